@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use tui::layout::Constraint;
+
+use crate::settings::PRIORITIES;
+use crate::transmission::TorrentInfo;
+use crate::utils::{
+    format_date, format_download_speed, format_eta, format_percent_done, format_ratio, format_size, format_status,
+    format_status_text, process_folder,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Column {
+    Status,
+    Name,
+    Done,
+    Eta,
+    Size,
+    Up,
+    Down,
+    Uploaded,
+    Downloaded,
+    Ratio,
+    Peers,
+    Queue,
+    Added,
+    DownloadDir,
+    StatusText,
+    Error,
+    Priority,
+}
+
+pub fn default_columns() -> Vec<Column> {
+    vec![
+        Column::Status,
+        Column::Name,
+        Column::Done,
+        Column::Eta,
+        Column::Size,
+        Column::Up,
+        Column::Down,
+        Column::Uploaded,
+    ]
+}
+
+// the name takes whatever is left, but never less than this
+const MIN_NAME_WIDTH: u16 = 20;
+
+impl Column {
+    pub fn title(self) -> &'static str {
+        match self {
+            Column::Status => " ",
+            Column::Name => "Name",
+            Column::Done => "Done",
+            Column::Eta => "Eta",
+            Column::Size => "Size",
+            Column::Up => "Up",
+            Column::Down => "Down",
+            Column::Uploaded => "Uploaded",
+            Column::Downloaded => "Downloaded",
+            Column::Ratio => "Ratio",
+            Column::Peers => "Peers",
+            Column::Queue => "Queue",
+            Column::Added => "Added",
+            Column::DownloadDir => "Directory",
+            Column::StatusText => "Status",
+            Column::Error => "Error",
+            Column::Priority => "Priority",
+        }
+    }
+
+    fn width(self) -> u16 {
+        match self {
+            Column::Status => 3,
+            Column::Name => MIN_NAME_WIDTH,
+            Column::Done => 5,
+            Column::Eta => 10,
+            Column::Size => 8,
+            Column::Up | Column::Down => 10,
+            Column::Uploaded | Column::Downloaded => 10,
+            Column::Ratio => 6,
+            Column::Peers | Column::Queue => 5,
+            Column::Added => 10,
+            Column::DownloadDir => 16,
+            Column::StatusText => 12,
+            Column::Error => 24,
+            Column::Priority => 8,
+        }
+    }
+
+    pub fn constraint(self) -> Constraint {
+        match self {
+            Column::Name => Constraint::Min(MIN_NAME_WIDTH),
+            _ => Constraint::Length(self.width()),
+        }
+    }
+
+    // the columns still shown when the terminal gets really narrow
+    fn essential(self) -> bool {
+        matches!(self, Column::Name | Column::Done | Column::Size | Column::Up | Column::Down)
+    }
+
+    // torrent-get fields that aren't part of TORRENT_INFO_FIELDS
+    pub fn extra_field(self) -> Option<&'static str> {
+        match self {
+            Column::Downloaded => Some("downloadedEver"),
+            Column::Priority => Some("bandwidthPriority"),
+            _ => None,
+        }
+    }
+
+    pub fn cell(self, x: &TorrentInfo, base_dir: &str) -> String {
+        match self {
+            Column::Status => format_status(&x.status, x.error).to_string(),
+            Column::Name => x.name.clone(),
+            Column::Done => format_percent_done(x.percent_done),
+            Column::Eta => format_eta(x.eta),
+            Column::Size => format_size(x.size_when_done),
+            Column::Up => format_download_speed(x.rate_upload, true),
+            Column::Down => format_download_speed(x.rate_download, true),
+            Column::Uploaded => format_size(x.uploaded_ever),
+            Column::Downloaded => format_size(x.downloaded_ever),
+            Column::Ratio => format_ratio(x.upload_ratio),
+            Column::Peers => x.peers_connected.to_string(),
+            Column::Queue => x.queue_position.to_string(),
+            Column::Added => format_date(x.added_date),
+            Column::DownloadDir => process_folder(&x.download_dir, base_dir),
+            Column::StatusText => format_status_text(&x.status).to_string(),
+            Column::Error => x.error_string.clone(),
+            Column::Priority => PRIORITIES
+                .get((x.bandwidth_priority + 1) as usize)
+                .unwrap_or(&"")
+                .to_string(),
+        }
+    }
+}
+
+// drops columns that don't fit, the optional ones from the right go first
+pub fn fit_columns(columns: &[Column], width: u16) -> Vec<Column> {
+    let mut visible = columns.to_vec();
+    loop {
+        let total: u16 = visible.iter().map(|x| x.width() + 1).sum();
+        if total <= width + 1 {
+            break;
+        }
+        let idx = visible
+            .iter()
+            .rposition(|x| !x.essential())
+            .or_else(|| visible.iter().rposition(|x| *x != Column::Name));
+        match idx {
+            Some(i) => {
+                visible.remove(i);
+            }
+            None => break,
+        }
+    }
+    visible
+}
+
+pub fn torrent_info_fields<'a>(base: &[&'a str], columns: &[Column]) -> Vec<&'a str> {
+    let mut fields = base.to_vec();
+    for field in columns.iter().filter_map(|x| x.extra_field()) {
+        if !fields.contains(&field) {
+            fields.push(field);
+        }
+    }
+    fields
+}
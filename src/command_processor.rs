@@ -1,9 +1,16 @@
+use crate::columns::torrent_info_fields;
 use crate::config::{Config, Connection};
-use crate::transmission::{FreeSpace, Result, Session, SessionStats, TorrentAdd, TorrentDetails, TransmissionClient};
+use crate::transmission::{
+    details_fields, format_tracker_list, DetailsTab, FreeSpace, Result, RpcError, Session, SessionStats, TorrentAdd, TorrentDetails, TorrentSet,
+    TorrentTrackers, TransmissionClient,
+};
+use crate::settings::{setting_keys, TORRENT_SETTINGS};
 use crate::utils::build_tree;
-use crossterm::event::{self, KeyEvent};
+use crossterm::event::{self, KeyEvent, MouseEvent};
 use lazy_static::lazy_static;
+use serde_json::json;
 //use procfs::process::Process;
+use std::fmt;
 use std::fs;
 use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
@@ -23,12 +30,107 @@ pub enum TorrentUpdate {
     ),
     Details(Box<TorrentDetails>),
     Input(KeyEvent),
+    Mouse(MouseEvent),
     UiTick,
     Err {
         msg: String,
         details: String,
+        connection: bool, // the daemon didn't answer the regular updates, the next one that comes means it's back
     },
     Session(Session),
+    TorrentAdded {
+        name: String,
+        duplicate: bool,
+    },
+    TorrentSettings(serde_json::Map<String, serde_json::Value>),
+    // acknowledgements of `TorrentCmd::Tracked`
+    Done {
+        cmd_id: CmdId,
+        op: Operation,
+        ids: Vec<i64>,
+    },
+    Failed {
+        cmd_id: CmdId,
+        op: Operation,
+        ids: Vec<i64>,
+        error: String,
+    },
+}
+
+pub type CmdId = u64;
+
+// a command over many torrents that went through for some of them, acknowledged as done for those
+// and failed for the rest
+#[derive(Debug)]
+struct PartialFailure {
+    done: Vec<i64>,
+    failed: Vec<i64>,
+    error: String, // the first one, the others tend to be the same
+}
+
+impl std::error::Error for PartialFailure {}
+
+impl fmt::Display for PartialFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+// what an acknowledged command does, named in the pending spinner and in the event log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Start,
+    StartNow,
+    Stop,
+    Verify,
+    Reannounce,
+    Remove,
+    RemoveWithData,
+    Move,
+    Queue,
+    Add,
+    Labels,
+    Files,
+    Settings,
+    Trackers,
+    ReplaceTrackers,
+    Rename,
+    SessionSettings,
+    Action,
+    FileAction,
+}
+
+impl Operation {
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::Start => "Start",
+            Operation::StartNow => "Start now",
+            Operation::Stop => "Pause",
+            Operation::Verify => "Verify",
+            Operation::Reannounce => "Reannounce",
+            Operation::Remove => "Remove",
+            Operation::RemoveWithData => "Remove with data",
+            Operation::Move => "Move",
+            Operation::Queue => "Queue move",
+            Operation::Add => "Add torrent",
+            Operation::Labels => "Set labels",
+            Operation::Files => "Change files",
+            Operation::Settings => "Torrent settings",
+            Operation::Trackers => "Edit trackers",
+            Operation::ReplaceTrackers => "Replace trackers",
+            Operation::Rename => "Rename",
+            Operation::SessionSettings => "Session settings",
+            Operation::Action => "Action",
+            Operation::FileAction => "File action",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackerCmd {
+    Add(String),
+    Remove(u64),
+    Replace(u64, String), // tracker id, new announce url
 }
 
 #[derive(Debug)]
@@ -37,6 +139,8 @@ pub enum TorrentCmd {
     Tick(u64),
     Action(i64, usize),
     GetDetails(i64),
+    SelectTab(DetailsTab), // fields of the following details updates
+    DetailsPane(bool),     // the pane next to the main table is shown
     Select(Option<i64>),
     QueueMoveUp(Vec<i64>),
     QueueMoveDown(Vec<i64>),
@@ -50,9 +154,57 @@ pub enum TorrentCmd {
     Reannounce(Vec<i64>),
     Move(Vec<i64>, String, bool),
     AddTorrent(Option<String>, Option<String>, Option<String>, bool), // download dir, filename, metainfo, start_paused
+    Set(Box<TorrentSet>),
+    GetSession,
+    GetTorrentSettings(i64),
+    Trackers(i64, TrackerCmd),
+    ReplaceTrackerPrefix(String, String),
+    SetSession(serde_json::Map<String, serde_json::Value>),
+    RenamePath(i64, String, String), // torrent id, path, new name
     //PoisonPill,
     Reconnect(usize),
-    FileAction(u64, usize, usize)
+    FileAction(u64, usize, usize),
+    Tracked(CmdId, Box<TorrentCmd>), // answered with TorrentUpdate::Done or Failed
+}
+
+impl TorrentCmd {
+    // commands worth acknowledging and the torrents they touch
+    pub fn operation(&self) -> Option<(Operation, Vec<i64>)> {
+        let op = match self {
+            TorrentCmd::Start(ids) => (Operation::Start, ids.clone()),
+            TorrentCmd::StartNow(ids) => (Operation::StartNow, ids.clone()),
+            TorrentCmd::Stop(ids) => (Operation::Stop, ids.clone()),
+            TorrentCmd::Verify(ids) => (Operation::Verify, ids.clone()),
+            TorrentCmd::Reannounce(ids) => (Operation::Reannounce, ids.clone()),
+            TorrentCmd::Delete(ids, false) => (Operation::Remove, ids.clone()),
+            TorrentCmd::Delete(ids, true) => (Operation::RemoveWithData, ids.clone()),
+            TorrentCmd::Move(ids, _, _) => (Operation::Move, ids.clone()),
+            TorrentCmd::QueueMoveUp(ids)
+            | TorrentCmd::QueueMoveDown(ids)
+            | TorrentCmd::QueueMoveTop(ids)
+            | TorrentCmd::QueueMoveBottom(ids) => (Operation::Queue, ids.clone()),
+            TorrentCmd::AddTorrent(..) => (Operation::Add, vec![]),
+            TorrentCmd::Set(x) if x.labels.is_some() => (Operation::Labels, x.ids.clone()),
+            TorrentCmd::Set(x)
+                if x.files_wanted.is_some()
+                    || x.files_unwanted.is_some()
+                    || x.priority_high.is_some()
+                    || x.priority_low.is_some()
+                    || x.priority_normal.is_some() =>
+            {
+                (Operation::Files, x.ids.clone())
+            }
+            TorrentCmd::Set(x) => (Operation::Settings, x.ids.clone()),
+            TorrentCmd::Trackers(id, _) => (Operation::Trackers, vec![*id]),
+            TorrentCmd::ReplaceTrackerPrefix(..) => (Operation::ReplaceTrackers, vec![]),
+            TorrentCmd::RenamePath(id, _, _) => (Operation::Rename, vec![*id]),
+            TorrentCmd::SetSession(_) => (Operation::SessionSettings, vec![]),
+            TorrentCmd::Action(id, _) => (Operation::Action, vec![*id]),
+            TorrentCmd::FileAction(id, _, _) => (Operation::FileAction, vec![*id as i64]),
+            _ => return None,
+        };
+        Some(op)
+    }
 }
 
 lazy_static! {
@@ -76,7 +228,10 @@ lazy_static! {
         "downloadDir",
         "uploadedEver",
         "uploadRatio",
-        "addedDate"
+        "addedDate",
+        "labels",
+        "isPrivate",
+        "doneDate"
     ];
 }
 
@@ -131,8 +286,14 @@ impl CommandProcessor {
                     }
 
                     if event::poll(timeout).expect("poll works") {
-                        if let event::Event::Key(key) = event::read().expect("can read events") {
-                            let _ = update_sender2.send(TorrentUpdate::Input(key)).await;
+                        match event::read().expect("can read events") {
+                            event::Event::Key(key) => {
+                                let _ = update_sender2.send(TorrentUpdate::Input(key)).await;
+                            }
+                            event::Event::Mouse(mouse) => {
+                                let _ = update_sender2.send(TorrentUpdate::Mouse(mouse)).await;
+                            }
+                            _ => {}
                         }
                     }
 
@@ -158,13 +319,13 @@ impl CommandProcessor {
                 // FIXME: technically incorrect, if client "comes back" after this the app will be
                 // in inconsistent state..
                 let _ = update_session(&client, &update_sender, &mut connection).await;
-                let _ = send_full_update(&client, &update_sender).await;
-                let mut details_id: Option<i64> = None;
+                let _ = send_full_update(&client, &update_sender, &config).await;
+                let mut details = DetailsRequest::default();
                 loop {
                     let result = update_step(
                         &mut receiver,
                         &update_sender,
-                        &mut details_id,
+                        &mut details,
                         &mut client,
                         &config,
                         &mut connection,
@@ -175,6 +336,7 @@ impl CommandProcessor {
                             .send(TorrentUpdate::Err {
                                 msg: "Communication failed".to_string(),
                                 details: error.to_string(),
+                                connection: false,
                             })
                             .await;
                     }
@@ -184,13 +346,18 @@ impl CommandProcessor {
     }
 }
 
-async fn send_full_update(client: &TransmissionClient, update_sender: &mpsc::Sender<TorrentUpdate>) -> Result<()> {
-    let res = client.get_all_torrents(&TORRENT_INFO_FIELDS).await;
+async fn send_full_update(
+    client: &TransmissionClient,
+    update_sender: &mpsc::Sender<TorrentUpdate>,
+    config: &Config,
+) -> Result<()> {
+    let res = client.get_all_torrents(&torrent_info_fields(&TORRENT_INFO_FIELDS, &config.columns)).await;
     if let Err(error) = res {
         let _ = update_sender
             .send(TorrentUpdate::Err {
                 msg: "Can't connect to transmission!".to_string(),
                 details: format!("Please, check connection string and restart the app:\n\n{}", error),
+                connection: true,
             })
             .await;
     } else {
@@ -201,6 +368,33 @@ async fn send_full_update(client: &TransmissionClient, update_sender: &mpsc::Sen
     Ok(())
 }
 
+// what the UI currently shows details of
+#[derive(Debug, Default)]
+struct DetailsRequest {
+    id: Option<i64>,
+    tab: DetailsTab,
+    pane: bool,
+}
+
+impl DetailsRequest {
+    fn fields(&self) -> Vec<&'static str> {
+        details_fields(self.tab, self.pane)
+    }
+}
+
+async fn send_details(
+    client: &TransmissionClient,
+    update_sender: &mpsc::Sender<TorrentUpdate>,
+    id: i64,
+    fields: &[&str],
+) -> Result<()> {
+    let details = client.get_torrent_details(vec![id], fields).await?; // TODO: what if id is wrong?
+    if let Some(details) = details.arguments.torrents.into_iter().next() {
+        update_sender.send(TorrentUpdate::Details(Box::new(details))).await?;
+    }
+    Ok(())
+}
+
 // a bit tricky config synchronization.., still better then Rc<Mutex>..
 async fn update_session(
     client: &TransmissionClient,
@@ -213,6 +407,7 @@ async fn update_session(
             .send(TorrentUpdate::Err {
                 msg: "Can't connect to transmission!".to_string(),
                 details: format!("Please, check connection string and restart the app:\n\n{}", error),
+                connection: true,
             })
             .await;
     } else {
@@ -220,6 +415,7 @@ async fn update_session(
         if connection.download_dir.is_empty() {
             connection.download_dir = response.arguments.download_dir.clone();
         }
+        client.set_rpc_version(response.arguments.rpc_version);
         let _ = update_sender.send(TorrentUpdate::Session(response.arguments)).await;
     }
     Ok(())
@@ -228,37 +424,95 @@ async fn update_session(
 async fn update_step(
     receiver: &mut mpsc::Receiver<TorrentCmd>,
     update_sender: &mpsc::Sender<TorrentUpdate>,
-    details_id: &mut Option<i64>,
+    details: &mut DetailsRequest,
     client: &mut TransmissionClient,
     config: &Config,
     connection: &mut Connection,
 ) -> Result<()> {
     let cmd = receiver.recv().await.expect("hmm, need to handle this error");
 
+    if let TorrentCmd::Tracked(cmd_id, cmd) = cmd {
+        let operation = cmd.operation();
+        let result = execute_cmd(*cmd, update_sender, details, client, config, connection).await;
+        match (operation, result) {
+            (Some((op, ids)), Ok(affected)) => {
+                let ids = affected.unwrap_or(ids);
+                update_sender.send(TorrentUpdate::Done { cmd_id, op, ids }).await?;
+            }
+            (Some((op, ids)), Err(error)) => {
+                let (ids, error) = match error.downcast::<PartialFailure>() {
+                    Ok(partial) => {
+                        let partial = *partial;
+                        if !partial.done.is_empty() {
+                            let ids = partial.done;
+                            update_sender.send(TorrentUpdate::Done { cmd_id, op, ids }).await?;
+                        }
+                        (partial.failed, partial.error)
+                    }
+                    Err(error) => (ids, error.to_string()),
+                };
+                update_sender.send(TorrentUpdate::Failed { cmd_id, op, ids, error }).await?;
+            }
+            (None, result) => {
+                result?;
+            }
+        }
+    } else {
+        // ticks fail when the daemon is gone, any other command just fails by itself
+        let connection_failed = matches!(cmd, TorrentCmd::Tick(_));
+        if let Err(error) = execute_cmd(cmd, update_sender, details, client, config, connection).await {
+            update_sender
+                .send(TorrentUpdate::Err {
+                    msg: "Communication failed".to_string(),
+                    details: error.to_string(),
+                    connection: connection_failed,
+                })
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+// returns the torrents a command changed when they are only known afterwards
+async fn execute_cmd(
+    cmd: TorrentCmd,
+    update_sender: &mpsc::Sender<TorrentUpdate>,
+    details: &mut DetailsRequest,
+    client: &mut TransmissionClient,
+    config: &Config,
+    connection: &mut Connection,
+) -> Result<Option<Vec<i64>>> {
     match cmd {
         TorrentCmd::Select(maybe_id) => {
-            *details_id = maybe_id;
+            details.id = maybe_id;
         }
         TorrentCmd::Reconnect(idx) => {
             *connection = config.connections[idx].clone();
             *client = TransmissionClient::new(&connection.url, &connection.username, &connection.password);
             let _ = update_session(client, update_sender, connection).await;
-            let _ = send_full_update(client, update_sender).await;
-            *details_id = None;
+            let _ = send_full_update(client, update_sender, config).await;
+            details.id = None;
         }
         TorrentCmd::GetDetails(id) => {
-            *details_id = Some(id);
-            let details = client.get_torrent_details(vec![id]).await?; // TODO: what if id is wrong?
-            if !details.arguments.torrents.is_empty() {
-                update_sender
-                    .send(TorrentUpdate::Details(Box::new(
-                        details.arguments.torrents[0].to_owned(),
-                    )))
-                    .await?;
+            details.id = Some(id);
+            send_details(client, update_sender, id, &details.fields()).await?;
+        }
+        TorrentCmd::SelectTab(tab) => {
+            details.tab = tab;
+            if let Some(id) = details.id {
+                send_details(client, update_sender, id, &details.fields()).await?;
+            }
+        }
+        TorrentCmd::DetailsPane(pane) => {
+            details.pane = pane;
+            if let Some(id) = details.id {
+                send_details(client, update_sender, id, &details.fields()).await?;
             }
         }
         TorrentCmd::Tick(i) => {
-            let resp = client.get_recent_torrents(&TORRENT_INFO_FIELDS).await?;
+            let resp = client
+                .get_recent_torrents(&torrent_info_fields(&TORRENT_INFO_FIELDS, &config.columns))
+                .await?;
             let torrents = resp.get("arguments").unwrap().get("torrents").unwrap().to_owned();
             let removed = resp.get("arguments").unwrap().get("removed").unwrap().to_owned();
 
@@ -278,11 +532,9 @@ async fn update_step(
 
             //let mem = page_size * (me_mem.resident - me_mem.shared);
             let mut maybe_details: Option<TorrentDetails> = None;
-            if let Some(id) = details_id {
-                let details = client.get_torrent_details(vec![*id]).await?; // TODO: what if id is wrong?
-                if !details.arguments.torrents.is_empty() {
-                    maybe_details = Some(details.arguments.torrents[0].to_owned());
-                }
+            if let Some(id) = details.id {
+                let resp = client.get_torrent_details(vec![id], &details.fields()).await?; // TODO: what if id is wrong?
+                maybe_details = resp.arguments.torrents.into_iter().next();
             }
             let _ = update_sender
                 .send(TorrentUpdate::Partial(
@@ -296,7 +548,7 @@ async fn update_step(
                 .await;
         }
         TorrentCmd::Action(id, idx) => {
-            let details = client.get_torrent_details(vec![id]).await?; // TODO: what if id is wrong?
+            let details = client.get_torrent_details(vec![id], &DetailsTab::Files.fields()).await?; // TODO: what if id is wrong?
             if !details.arguments.torrents.is_empty() {
                 let torrent = &details.arguments.torrents[0];
                 let location = if !connection.local_download_dir.is_empty() {
@@ -378,17 +630,122 @@ async fn update_step(
                 priority_normal: None,
             };
             let res = client.torrent_add(&tadd).await?;
-            let _ = res
-                .as_object()
-                .expect("should return object")
-                .get("result")
-                .expect("must result")
-                .as_str()
-                .unwrap()
-                .to_string();
+            let added = match (res.arguments.torrent_added, res.arguments.torrent_duplicate) {
+                (Some(t), _) => Some((t.name, false)),
+                (None, Some(t)) => Some((t.name, true)),
+                (None, None) => None,
+            };
+            if let Some((name, duplicate)) = added {
+                update_sender
+                    .send(TorrentUpdate::TorrentAdded { name, duplicate })
+                    .await?;
+            }
+        }
+        TorrentCmd::RenamePath(id, path, name) => {
+            let res = client.rename_path(id, &path, &name).await;
+            // the tree goes back to the old name if it failed
+            send_details(client, update_sender, id, &DetailsTab::Files.fields()).await?;
+            res.map_err(|error| format!("Can't rename {}: {}", path, error))?;
+        }
+        TorrentCmd::Set(torrent_set) => {
+            client.torrent_set(&torrent_set).await?;
+        }
+        TorrentCmd::GetSession => {
+            update_session(client, update_sender, connection).await?;
+        }
+        TorrentCmd::GetTorrentSettings(id) => {
+            let res = client.get_torrent_fields(vec![id], &setting_keys(TORRENT_SETTINGS)).await?;
+            if let Some(settings) = res.arguments.torrents.into_iter().next() {
+                update_sender.send(TorrentUpdate::TorrentSettings(settings)).await?;
+            }
+        }
+        TorrentCmd::Trackers(id, tracker_cmd) => {
+            let torrent_set = if client.supports_tracker_list() {
+                let res = client.get_torrent_trackers(vec![id]).await?;
+                let trackers: Vec<_> = res.arguments.torrents.into_iter().flat_map(|t| t.trackers).collect();
+                let list: Vec<(u64, String)> = match tracker_cmd {
+                    TrackerCmd::Add(url) => {
+                        let tier = trackers.iter().map(|t| t.tier + 1).max().unwrap_or(0);
+                        let mut list: Vec<_> = trackers.into_iter().map(|t| (t.tier, t.announce)).collect();
+                        list.push((tier, url));
+                        list
+                    }
+                    TrackerCmd::Remove(tracker_id) => trackers
+                        .into_iter()
+                        .filter(|t| t.id != tracker_id)
+                        .map(|t| (t.tier, t.announce))
+                        .collect(),
+                    TrackerCmd::Replace(tracker_id, url) => trackers
+                        .into_iter()
+                        .map(|t| (t.tier, if t.id == tracker_id { url.clone() } else { t.announce }))
+                        .collect(),
+                };
+                TorrentSet {
+                    ids: vec![id],
+                    tracker_list: Some(format_tracker_list(&list)),
+                    ..TorrentSet::default()
+                }
+            } else {
+                match tracker_cmd {
+                    TrackerCmd::Add(url) => TorrentSet {
+                        ids: vec![id],
+                        tracker_add: Some(vec![url]),
+                        ..TorrentSet::default()
+                    },
+                    TrackerCmd::Remove(tracker_id) => TorrentSet {
+                        ids: vec![id],
+                        tracker_remove: Some(vec![tracker_id]),
+                        ..TorrentSet::default()
+                    },
+                    TrackerCmd::Replace(tracker_id, url) => TorrentSet {
+                        ids: vec![id],
+                        tracker_replace: Some(vec![json!(tracker_id), json!(url)]),
+                        ..TorrentSet::default()
+                    },
+                }
+            };
+            client.torrent_set(&torrent_set).await?;
+        }
+        TorrentCmd::ReplaceTrackerPrefix(from, to) => {
+            let res = client.get_torrent_trackers(vec![]).await?;
+            // the daemon refusing one torrent doesn't stop the others, the daemon going away does
+            let mut replaced = vec![];
+            let mut failed = vec![];
+            let mut first_error = None;
+            for torrent in res.arguments.torrents {
+                if let Some(torrent_set) = replace_tracker_prefix(client, &torrent, &from, &to) {
+                    match client.torrent_set(&torrent_set).await {
+                        Ok(_) => replaced.push(torrent.id),
+                        Err(error) if error.is::<RpcError>() => {
+                            failed.push(torrent.id);
+                            first_error.get_or_insert_with(|| error.to_string());
+                        }
+                        Err(error) => {
+                            failed.push(torrent.id);
+                            return Err(Box::new(PartialFailure {
+                                done: replaced,
+                                failed,
+                                error: format!("{}, the remaining torrents weren't tried", error),
+                            }));
+                        }
+                    }
+                }
+            }
+            if let Some(error) = first_error {
+                return Err(Box::new(PartialFailure {
+                    done: replaced,
+                    failed,
+                    error,
+                }));
+            }
+            return Ok(Some(replaced));
+        }
+        TorrentCmd::SetSession(settings) => {
+            client.session_set(&settings).await?;
+            update_session(client, update_sender, connection).await?;
         }
         TorrentCmd::FileAction(id, action_idx, file_idx) => {
-            let details = client.get_torrent_details(vec![id as i64]).await?; // TODO: what if id is wrong?
+            let details = client.get_torrent_details(vec![id as i64], &DetailsTab::Files.fields()).await?; // TODO: what if id is wrong?
             if !details.arguments.torrents.is_empty() {
                 let torrent = &details.arguments.torrents[0];
                 let location = if !connection.local_download_dir.is_empty() {
@@ -416,8 +773,42 @@ async fn update_step(
                 cmd_builder.spawn()?; // TODO: differentiate between different kind of errors
             }
         }
+        TorrentCmd::Tracked(..) => {} // unwrapped in update_step
     };
-    Ok(())
+    Ok(None)
 }
 
-
+fn replace_tracker_prefix(
+    client: &TransmissionClient,
+    torrent: &TorrentTrackers,
+    from: &str,
+    to: &str,
+) -> Option<TorrentSet> {
+    let replace = |announce: &str| announce.strip_prefix(from).map(|rest| to.to_owned() + rest);
+    if !torrent.trackers.iter().any(|t| t.announce.starts_with(from)) {
+        None
+    } else if client.supports_tracker_list() {
+        let list: Vec<(u64, String)> = torrent
+            .trackers
+            .iter()
+            .map(|t| (t.tier, replace(&t.announce).unwrap_or_else(|| t.announce.clone())))
+            .collect();
+        Some(TorrentSet {
+            ids: vec![torrent.id],
+            tracker_list: Some(format_tracker_list(&list)),
+            ..TorrentSet::default()
+        })
+    } else {
+        let pairs = torrent
+            .trackers
+            .iter()
+            .filter_map(|t| replace(&t.announce).map(|url| vec![json!(t.id), json!(url)]))
+            .flatten()
+            .collect();
+        Some(TorrentSet {
+            ids: vec![torrent.id],
+            tracker_replace: Some(pairs),
+            ..TorrentSet::default()
+        })
+    }
+}
@@ -1,7 +1,9 @@
 use home::home_dir;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs::{create_dir_all, write, File};
 use std::io::BufReader;
+use crate::columns::{default_columns, Column};
 use tui::style::{Color, Style, Modifier};
 
 pub struct Styles {
@@ -45,6 +47,13 @@ pub struct Action {
     pub args: Vec<String>,
 }
 
+// a named filter in the sidebar, `query` uses the search syntax
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct View {
+    pub name: String,
+    pub query: String,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DirMapping {
     pub label: String,
@@ -57,6 +66,9 @@ pub struct DirMapping {
 }
 
 fn truth() -> bool { true }
+fn recent_count() -> usize { 120 }
+fn finished_days() -> u64 { 7 }
+fn details_pane() -> u16 { 200 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Connection {
@@ -106,6 +118,24 @@ pub struct Config {
     #[serde(rename = "show-icons")]
     #[serde(default = "truth")]
     pub show_icons: bool,
+    // main table columns, left to right. Plain values go before the tables, or toml can't write the config
+    #[serde(default = "default_columns")]
+    pub columns: Vec<Column>,
+    // the Recent filter shows the newest torrents, at most `recent-count`, only the last `recent-days` if set
+    #[serde(rename = "recent-count")]
+    #[serde(default = "recent_count")]
+    pub recent_count: usize,
+    #[serde(rename = "recent-days")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recent_days: Option<u64>,
+    #[serde(rename = "finished-days")]
+    #[serde(default = "finished_days")]
+    pub finished_days: u64,
+    // terminals at least this wide show the selected torrent next to the main table, 0 turns it off
+    #[serde(rename = "details-pane")]
+    #[serde(default = "details_pane")]
+    pub details_pane: u16,
     pub connections: Vec<Connection>,
     #[serde(default)]
     #[serde(skip_serializing_if = "Vec::is_empty")]
@@ -114,8 +144,15 @@ pub struct Config {
     #[serde(rename = "file-actions")]
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub file_actions: Vec<Action>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub views: Vec<View>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub colors: Option<Colors>
+    pub colors: Option<Colors>,
+    // mode -> action name -> key chords, see keymap.rs for the defaults
+    #[serde(default)]
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub keys: HashMap<String, HashMap<String, Vec<String>>>,
 }
 
 fn empty_config() -> Config {
@@ -132,8 +169,15 @@ fn empty_config() -> Config {
         show_icons: true,
         actions: vec![],
         file_actions: vec![],
+        views: vec![],
         traffic_monitor: TrafficMonitorOptions::Upload,
-        colors: None
+        colors: None,
+        columns: default_columns(),
+        recent_count: recent_count(),
+        recent_days: None,
+        finished_days: finished_days(),
+        details_pane: details_pane(),
+        keys: HashMap::new(),
     }
 }
 pub fn get_or_create_config() -> Result<Config, Box<dyn std::error::Error>> {
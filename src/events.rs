@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+
+use crate::utils::unix_now;
+
+// older events are dropped
+const MAX_EVENTS: usize = 500;
+// seconds a toast stays on screen
+const TOAST_SECS: i64 = 5;
+const ERROR_TOAST_SECS: i64 = 10;
+pub const MAX_TOASTS: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    Error,        // failed requests and the like
+    Info,         // results of commands
+    Connected,
+    Added,
+    Completed,
+    Removed,
+    TorrentError, // a torrent reported an error
+}
+
+impl EventKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            EventKind::Error => "Error",
+            EventKind::Info => "Info",
+            EventKind::Connected => "Connected",
+            EventKind::Added => "Added",
+            EventKind::Completed => "Completed",
+            EventKind::Removed => "Removed",
+            EventKind::TorrentError => "Torrent error",
+        }
+    }
+
+    pub fn is_error(self) -> bool {
+        matches!(self, EventKind::Error | EventKind::TorrentError)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub time: i64, // when it first happened, the toast times out from here
+    pub kind: EventKind,
+    pub title: String,
+    pub details: String,
+    pub count: usize, // the same event in a row is kept once
+    pub last: i64,
+}
+
+impl Event {
+    pub fn title(&self) -> String {
+        if self.count > 1 {
+            format!("{} (×{})", self.title, self.count)
+        } else {
+            self.title.clone()
+        }
+    }
+
+    fn toast_secs(&self) -> i64 {
+        if self.kind.is_error() {
+            ERROR_TOAST_SECS
+        } else {
+            TOAST_SECS
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct EventLog {
+    events: VecDeque<Event>,
+    dismissed: usize, // events pushed before the last dismiss don't show as toasts
+}
+
+impl EventLog {
+    pub fn push(&mut self, kind: EventKind, title: impl Into<String>, details: impl Into<String>) {
+        let (title, details) = (title.into(), details.into());
+        let now = unix_now();
+        // every failed tick reports the same error, they add up on the last event
+        if let Some(last) = self.events.back_mut() {
+            if last.kind == kind && last.title == title && last.details == details {
+                last.count += 1;
+                last.last = now;
+                return;
+            }
+        }
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+            self.dismissed = self.dismissed.saturating_sub(1);
+        }
+        self.events.push_back(Event {
+            time: now,
+            kind,
+            title,
+            details,
+            count: 1,
+            last: now,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    // newest first
+    pub fn iter(&self) -> impl Iterator<Item = &Event> {
+        self.events.iter().rev()
+    }
+
+    // newest first, still fresh and not dismissed
+    pub fn toasts(&self, now: i64) -> Vec<&Event> {
+        self.events
+            .iter()
+            .skip(self.dismissed)
+            .rev()
+            .filter(|x| now - x.time < x.toast_secs())
+            .take(MAX_TOASTS)
+            .collect()
+    }
+
+    pub fn dismiss_toasts(&mut self) {
+        self.dismissed = self.events.len();
+    }
+}
@@ -0,0 +1,538 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+
+use crate::config::Action;
+use crate::{SortKey, Transition};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mode {
+    Global, // every screen except the text inputs
+    Main,
+    Filter,
+    Sort,
+    Action,
+    Details, // the general and pieces tabs
+    Files,
+    FileAction,
+    Trackers,
+    Peers,
+    Settings,
+    Log,
+}
+
+impl Mode {
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Global => "global",
+            Mode::Main => "main",
+            Mode::Filter => "filter",
+            Mode::Sort => "sort",
+            Mode::Action => "action",
+            Mode::Details => "details",
+            Mode::Files => "files",
+            Mode::FileAction => "file-action",
+            Mode::Trackers => "trackers",
+            Mode::Peers => "peers",
+            Mode::Settings => "settings",
+            Mode::Log => "log",
+        }
+    }
+
+    // folder, label and view letters of the filter menu are generated, they take what's left
+    pub fn of(transition: &Transition) -> Option<Mode> {
+        match transition {
+            Transition::MainScreen => Some(Mode::Main),
+            Transition::Filter => Some(Mode::Filter),
+            Transition::ChooseSortFunc => Some(Mode::Sort),
+            Transition::Action => Some(Mode::Action),
+            Transition::General | Transition::Pieces => Some(Mode::Details),
+            Transition::Files => Some(Mode::Files),
+            Transition::FileAction => Some(Mode::FileAction),
+            Transition::Trackers => Some(Mode::Trackers),
+            Transition::Peers => Some(Mode::Peers),
+            Transition::Settings => Some(Mode::Settings),
+            Transition::Log => Some(Mode::Log),
+            _ => None,
+        }
+    }
+}
+
+static MODES: &[Mode] = &[
+    Mode::Global,
+    Mode::Main,
+    Mode::Filter,
+    Mode::Sort,
+    Mode::Action,
+    Mode::Details,
+    Mode::Files,
+    Mode::FileAction,
+    Mode::Trackers,
+    Mode::Peers,
+    Mode::Settings,
+    Mode::Log,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyAction {
+    Quit,
+    Help,
+    Next,
+    Prev,
+    Back,
+    ActionMenu,
+    Connections,
+    Filter,
+    Search,
+    FindNext,
+    FindPrev,
+    Details,
+    Sort,
+    AddTorrent,
+    SessionSettings,
+    Mark,
+    MarkRange,
+    UnmarkAll,
+    Start,
+    StartNow,
+    Stop,
+    Verify,
+    Move,
+    TorrentSettings,
+    Labels,
+    Remove,
+    RemoveWithData,
+    QueueUp,
+    QueueDown,
+    QueueTop,
+    QueueBottom,
+    Collapse,
+    Expand,
+    Toggle,
+    FileActions,
+    Files,
+    Trackers,
+    Peers,
+    NextTab,
+    PrevTab,
+    Tab(usize), // position in DETAILS_TABS
+    Download,
+    Skip,
+    LowPriority,
+    NormalPriority,
+    HighPriority,
+    Rename,
+    AddTracker,
+    EditTracker,
+    RemoveTracker,
+    ReplaceTrackers,
+    SortColumn,
+    ReverseSort,
+    Edit,
+    Save,
+    EventLog,
+    BuiltinFilter(usize), // index into the built-in filters of the sidebar
+    RemoveFilter,
+    SortBy(SortKey),
+    Invert,
+    SecondaryKey,
+}
+
+struct Binding {
+    mode: Mode,
+    action: KeyAction,
+    name: &'static str,
+    keys: &'static [&'static str],
+    description: &'static str,
+}
+
+const fn bind(
+    mode: Mode,
+    action: KeyAction,
+    name: &'static str,
+    keys: &'static [&'static str],
+    description: &'static str,
+) -> Binding {
+    Binding {
+        mode,
+        action,
+        name,
+        keys,
+        description,
+    }
+}
+
+// default bindings, also the list of what can be rebound in each mode
+static DEFAULT_BINDINGS: &[Binding] = &[
+    bind(Mode::Global, KeyAction::Quit, "quit", &["q"], "Quit"),
+    bind(Mode::Global, KeyAction::Help, "help", &["f1"], "Help screen"),
+    bind(Mode::Main, KeyAction::Prev, "prev", &["k", "up"], "Prev item"),
+    bind(Mode::Main, KeyAction::Next, "next", &["j", "down"], "Next item"),
+    bind(Mode::Main, KeyAction::Filter, "filter", &["f"], "Filter menu"),
+    bind(Mode::Main, KeyAction::Sort, "sort", &["S"], "Sort menu"),
+    bind(Mode::Main, KeyAction::ActionMenu, "action-menu", &["space"], "Action menu"),
+    bind(Mode::Main, KeyAction::Details, "details", &["d"], "Details screen"),
+    bind(Mode::Main, KeyAction::FindNext, "find-next", &["/"], "Find next item in list"),
+    bind(Mode::Main, KeyAction::FindPrev, "find-prev", &["?"], "Find prev item in list"),
+    bind(Mode::Main, KeyAction::Search, "search", &["s"], "Search across all torrents"),
+    bind(Mode::Main, KeyAction::Connections, "connections", &["c"], "Connection menu"),
+    bind(Mode::Main, KeyAction::AddTorrent, "add-torrent", &["a"], "Add torrent"),
+    bind(Mode::Main, KeyAction::SessionSettings, "session-settings", &["o"], "Session settings"),
+    bind(Mode::Main, KeyAction::Mark, "mark", &["m"], "Mark / unmark torrent"),
+    bind(Mode::Main, KeyAction::MarkRange, "mark-range", &["v"], "Mark a range of torrents"),
+    bind(Mode::Main, KeyAction::UnmarkAll, "unmark-all", &["u"], "Unmark all"),
+    bind(Mode::Main, KeyAction::EventLog, "event-log", &["L"], "Event log"),
+    bind(Mode::Main, KeyAction::Back, "back", &["esc"], "Cancel range or search, hide notifications"),
+    bind(Mode::Filter, KeyAction::Back, "back", &["esc"], "Close menu"),
+    bind(Mode::Filter, KeyAction::RemoveFilter, "remove-filter", &["backspace"], "Drop the last filter"),
+    bind(Mode::Filter, KeyAction::Collapse, "collapse", &["left"], "Close folder"),
+    bind(Mode::Filter, KeyAction::Expand, "expand", &["right"], "Open folder"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(0), "recent", &["R"], "Recent"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(1), "active", &["A"], "Active"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(2), "paused", &["P"], "Paused"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(3), "checking-queue", &["G"], "Checking queue"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(4), "checking", &["C"], "Checking"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(5), "download-queue", &["Q"], "Download queue"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(6), "downloading", &["D"], "Downloading"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(7), "seeding-queue", &["U"], "Seeding queue"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(8), "seeding", &["S"], "Seeding"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(9), "error", &["E"], "Error"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(10), "stalled", &["T"], "Stalled"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(11), "finished", &["F"], "Finished"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(12), "incomplete", &["I"], "Incomplete"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(13), "private", &["V"], "Private"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(14), "metadata", &["M"], "Awaiting metadata"),
+    bind(Mode::Filter, KeyAction::BuiltinFilter(15), "all", &["L"], "All"),
+    bind(Mode::Sort, KeyAction::Back, "back", &["esc"], "Close menu"),
+    bind(Mode::Sort, KeyAction::SortBy(SortKey::DateAdded), "date-added", &["d"], "By date added (default)"),
+    bind(Mode::Sort, KeyAction::SortBy(SortKey::Name), "name", &["n"], "By name"),
+    bind(Mode::Sort, KeyAction::SortBy(SortKey::Size), "size", &["s"], "By size"),
+    bind(Mode::Sort, KeyAction::SortBy(SortKey::Progress), "progress", &["p"], "By progress"),
+    bind(Mode::Sort, KeyAction::SortBy(SortKey::Eta), "eta", &["e"], "By eta"),
+    bind(Mode::Sort, KeyAction::SortBy(SortKey::Ratio), "ratio", &["r"], "By ratio"),
+    bind(Mode::Sort, KeyAction::SortBy(SortKey::Uploaded), "uploaded", &["u"], "By uploaded total"),
+    bind(Mode::Sort, KeyAction::SortBy(SortKey::UploadSpeed), "upload-speed", &["U"], "By upload speed"),
+    bind(Mode::Sort, KeyAction::SortBy(SortKey::DownloadSpeed), "download-speed", &["D"], "By download speed"),
+    bind(Mode::Sort, KeyAction::SortBy(SortKey::Queue), "queue", &["Q"], "By queue position"),
+    bind(Mode::Sort, KeyAction::SortBy(SortKey::Status), "status", &["t"], "By status"),
+    bind(Mode::Sort, KeyAction::SortBy(SortKey::Directory), "folder", &["f"], "By folder"),
+    bind(Mode::Sort, KeyAction::Invert, "invert", &["i"], "Invert order"),
+    bind(Mode::Sort, KeyAction::SecondaryKey, "secondary-key", &["tab"], "Switch to the secondary key and back"),
+    bind(Mode::Action, KeyAction::Back, "back", &["space", "esc"], "Close menu"),
+    bind(Mode::Action, KeyAction::Start, "start", &["s"], "Start"),
+    bind(Mode::Action, KeyAction::StartNow, "start-now", &["S"], "Start now"),
+    bind(Mode::Action, KeyAction::Stop, "stop", &["p"], "Pause"),
+    bind(Mode::Action, KeyAction::Verify, "verify", &["v"], "Verify"),
+    bind(Mode::Action, KeyAction::Move, "move", &["m"], "Move"),
+    bind(Mode::Action, KeyAction::TorrentSettings, "settings", &["e"], "Settings"),
+    bind(Mode::Action, KeyAction::Labels, "labels", &["l"], "Labels"),
+    bind(Mode::Action, KeyAction::Remove, "remove", &["x"], "Remove"),
+    bind(Mode::Action, KeyAction::RemoveWithData, "remove-with-data", &["X"], "Remove with data"),
+    bind(Mode::Action, KeyAction::QueueUp, "queue-up", &["k"], "Queue up"),
+    bind(Mode::Action, KeyAction::QueueDown, "queue-down", &["j"], "Queue down"),
+    bind(Mode::Action, KeyAction::QueueTop, "queue-top", &["K"], "Queue top"),
+    bind(Mode::Action, KeyAction::QueueBottom, "queue-bottom", &["J"], "Queue bottom"),
+    bind(Mode::Details, KeyAction::Back, "back", &["esc", "d"], "Back to the list"),
+    bind(Mode::Details, KeyAction::NextTab, "next-tab", &["tab"], "Next tab"),
+    bind(Mode::Details, KeyAction::PrevTab, "prev-tab", &["backtab"], "Prev tab"),
+    bind(Mode::Details, KeyAction::Tab(0), "general-tab", &["1"], "General tab"),
+    bind(Mode::Details, KeyAction::Tab(1), "files-tab", &["2"], "Files tab"),
+    bind(Mode::Details, KeyAction::Tab(2), "peers-tab", &["3"], "Peers tab"),
+    bind(Mode::Details, KeyAction::Tab(3), "trackers-tab", &["4"], "Trackers tab"),
+    bind(Mode::Details, KeyAction::Tab(4), "pieces-tab", &["5"], "Pieces tab"),
+    bind(Mode::Details, KeyAction::Files, "files", &["f"], "Files"),
+    bind(Mode::Details, KeyAction::Peers, "peers", &["p"], "Peers"),
+    bind(Mode::Details, KeyAction::Trackers, "trackers", &["t"], "Trackers"),
+    bind(Mode::Files, KeyAction::Back, "back", &["esc", "d"], "Back to the list"),
+    bind(Mode::Files, KeyAction::NextTab, "next-tab", &["tab"], "Next tab"),
+    bind(Mode::Files, KeyAction::PrevTab, "prev-tab", &["backtab"], "Prev tab"),
+    bind(Mode::Files, KeyAction::Tab(0), "general-tab", &["1"], "General tab"),
+    bind(Mode::Files, KeyAction::Tab(1), "files-tab", &["2"], "Files tab"),
+    bind(Mode::Files, KeyAction::Tab(2), "peers-tab", &["3"], "Peers tab"),
+    bind(Mode::Files, KeyAction::Tab(3), "trackers-tab", &["4"], "Trackers tab"),
+    bind(Mode::Files, KeyAction::Tab(4), "pieces-tab", &["5"], "Pieces tab"),
+    bind(Mode::Files, KeyAction::Prev, "prev", &["k", "up"], "Prev item"),
+    bind(Mode::Files, KeyAction::Next, "next", &["j", "down"], "Next item"),
+    bind(Mode::Files, KeyAction::Collapse, "collapse", &["h", "left"], "Close folder"),
+    bind(Mode::Files, KeyAction::Expand, "expand", &["l", "right"], "Open folder"),
+    bind(Mode::Files, KeyAction::Toggle, "toggle", &["enter"], "Open / close folder"),
+    bind(Mode::Files, KeyAction::FileActions, "file-actions", &["space"], "File actions"),
+    bind(Mode::Files, KeyAction::Trackers, "trackers", &["t"], "Trackers"),
+    bind(Mode::Files, KeyAction::Peers, "peers", &["p"], "Peers"),
+    bind(Mode::Files, KeyAction::Filter, "filter", &["/"], "Filter files"),
+    bind(Mode::Files, KeyAction::SortColumn, "sort", &["S"], "Sort by name, size or completion"),
+    bind(Mode::Files, KeyAction::ReverseSort, "reverse", &["r"], "Reverse order"),
+    bind(Mode::FileAction, KeyAction::Back, "back", &["esc"], "Close menu"),
+    bind(Mode::FileAction, KeyAction::Download, "download", &["+"], "Download"),
+    bind(Mode::FileAction, KeyAction::Skip, "skip", &["-"], "Skip"),
+    bind(Mode::FileAction, KeyAction::Rename, "rename", &["r"], "Rename"),
+    bind(Mode::FileAction, KeyAction::LowPriority, "low-priority", &["l"], "Low Priority"),
+    bind(Mode::FileAction, KeyAction::NormalPriority, "normal-priority", &["m"], "Normal Priority"),
+    bind(Mode::FileAction, KeyAction::HighPriority, "high-priority", &["h"], "High Priority"),
+    bind(Mode::Trackers, KeyAction::Back, "back", &["esc", "d"], "Back to the list"),
+    bind(Mode::Trackers, KeyAction::NextTab, "next-tab", &["tab"], "Next tab"),
+    bind(Mode::Trackers, KeyAction::PrevTab, "prev-tab", &["backtab"], "Prev tab"),
+    bind(Mode::Trackers, KeyAction::Tab(0), "general-tab", &["1"], "General tab"),
+    bind(Mode::Trackers, KeyAction::Tab(1), "files-tab", &["2"], "Files tab"),
+    bind(Mode::Trackers, KeyAction::Tab(2), "peers-tab", &["3"], "Peers tab"),
+    bind(Mode::Trackers, KeyAction::Tab(3), "trackers-tab", &["4"], "Trackers tab"),
+    bind(Mode::Trackers, KeyAction::Tab(4), "pieces-tab", &["5"], "Pieces tab"),
+    bind(Mode::Trackers, KeyAction::Files, "files", &["t", "f"], "Files"),
+    bind(Mode::Trackers, KeyAction::Peers, "peers", &["p"], "Peers"),
+    bind(Mode::Trackers, KeyAction::Prev, "prev", &["k", "up"], "Prev tracker"),
+    bind(Mode::Trackers, KeyAction::Next, "next", &["j", "down"], "Next tracker"),
+    bind(Mode::Trackers, KeyAction::AddTracker, "add", &["a"], "Add tracker"),
+    bind(Mode::Trackers, KeyAction::EditTracker, "edit", &["e", "enter"], "Edit tracker"),
+    bind(Mode::Trackers, KeyAction::RemoveTracker, "remove", &["x"], "Remove tracker"),
+    bind(Mode::Trackers, KeyAction::ReplaceTrackers, "replace", &["R"], "Replace in all torrents"),
+    bind(Mode::Peers, KeyAction::Back, "back", &["esc", "d"], "Back to the list"),
+    bind(Mode::Peers, KeyAction::NextTab, "next-tab", &["tab"], "Next tab"),
+    bind(Mode::Peers, KeyAction::PrevTab, "prev-tab", &["backtab"], "Prev tab"),
+    bind(Mode::Peers, KeyAction::Tab(0), "general-tab", &["1"], "General tab"),
+    bind(Mode::Peers, KeyAction::Tab(1), "files-tab", &["2"], "Files tab"),
+    bind(Mode::Peers, KeyAction::Tab(2), "peers-tab", &["3"], "Peers tab"),
+    bind(Mode::Peers, KeyAction::Tab(3), "trackers-tab", &["4"], "Trackers tab"),
+    bind(Mode::Peers, KeyAction::Tab(4), "pieces-tab", &["5"], "Pieces tab"),
+    bind(Mode::Peers, KeyAction::Files, "files", &["f"], "Files"),
+    bind(Mode::Peers, KeyAction::Trackers, "trackers", &["t"], "Trackers"),
+    bind(Mode::Peers, KeyAction::Prev, "prev", &["k", "up"], "Prev peer"),
+    bind(Mode::Peers, KeyAction::Next, "next", &["j", "down"], "Next peer"),
+    bind(Mode::Peers, KeyAction::SortColumn, "sort", &["S"], "Sort by next column"),
+    bind(Mode::Peers, KeyAction::ReverseSort, "reverse", &["r"], "Reverse order"),
+    bind(Mode::Settings, KeyAction::Back, "back", &["esc", "o"], "Close"),
+    bind(Mode::Settings, KeyAction::Prev, "prev", &["k", "up"], "Prev setting"),
+    bind(Mode::Settings, KeyAction::Next, "next", &["j", "down"], "Next setting"),
+    bind(Mode::Settings, KeyAction::Edit, "edit", &["enter", "space"], "Toggle / edit"),
+    bind(Mode::Settings, KeyAction::Save, "save", &["w"], "Save torrent settings"),
+    bind(Mode::Log, KeyAction::Back, "back", &["esc", "L"], "Close"),
+    bind(Mode::Log, KeyAction::Prev, "prev", &["k", "up"], "Prev event"),
+    bind(Mode::Log, KeyAction::Next, "next", &["j", "down"], "Next event"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl KeyChord {
+    // "j", "S", "space", "ctrl-d", "alt-enter", "f1", ...
+    pub fn parse(s: &str) -> Result<KeyChord, String> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        loop {
+            if let Some(x) = rest.strip_prefix("ctrl-") {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = x;
+            } else if let Some(x) = rest.strip_prefix("alt-") {
+                modifiers |= KeyModifiers::ALT;
+                rest = x;
+            } else {
+                break;
+            }
+        }
+        let code = match rest {
+            "space" => KeyCode::Char(' '),
+            "enter" => KeyCode::Enter,
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "backspace" => KeyCode::Backspace,
+            "delete" => KeyCode::Delete,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            _ if rest.chars().count() == 1 => KeyCode::Char(rest.chars().next().unwrap_or(' ')),
+            _ => match rest.strip_prefix('f').and_then(|x| x.parse::<u8>().ok()) {
+                Some(n) if (1..=12).contains(&n) => KeyCode::F(n),
+                _ => return Err(format!("unknown key '{}'", s)),
+            },
+        };
+        Ok(KeyChord { code, modifiers })
+    }
+
+    fn from_event(event: &KeyEvent) -> KeyChord {
+        // shift is already in the character itself, and in backtab
+        let modifiers = match event.code {
+            KeyCode::Char(_) | KeyCode::BackTab => event.modifiers - KeyModifiers::SHIFT,
+            _ => event.modifiers,
+        };
+        KeyChord {
+            code: event.code,
+            modifiers,
+        }
+    }
+
+    pub fn format(&self) -> String {
+        let key = match self.code {
+            KeyCode::Char(' ') => "space".to_string(),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Enter => "enter".to_string(),
+            KeyCode::Esc => "esc".to_string(),
+            KeyCode::Tab => "tab".to_string(),
+            KeyCode::BackTab => "backtab".to_string(),
+            KeyCode::Backspace => "backspace".to_string(),
+            KeyCode::Delete => "delete".to_string(),
+            KeyCode::Up => "↑".to_string(),
+            KeyCode::Down => "↓".to_string(),
+            KeyCode::Left => "←".to_string(),
+            KeyCode::Right => "→".to_string(),
+            KeyCode::Home => "home".to_string(),
+            KeyCode::End => "end".to_string(),
+            KeyCode::PageUp => "pageup".to_string(),
+            KeyCode::PageDown => "pagedown".to_string(),
+            KeyCode::F(n) => format!("F{}", n),
+            other => format!("{:?}", other),
+        };
+        let mut prefix = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            prefix += "ctrl-";
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            prefix += "alt-";
+        }
+        prefix + &key
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    keys: HashMap<(Mode, KeyChord), KeyAction>,
+    chords: HashMap<(Mode, KeyAction), Vec<KeyChord>>,
+}
+
+impl Keymap {
+    // `config` is the [keys] section: mode -> action name -> key chords
+    pub fn new(config: &HashMap<String, HashMap<String, Vec<String>>>) -> Result<Keymap, String> {
+        let mut chords: HashMap<(Mode, KeyAction), Vec<KeyChord>> = HashMap::new();
+        for b in DEFAULT_BINDINGS {
+            let keys = b.keys.iter().map(|k| KeyChord::parse(k)).collect::<Result<Vec<_>, _>>()?;
+            chords.insert((b.mode, b.action), keys);
+        }
+        for (mode_name, actions) in config {
+            let mode = MODES
+                .iter()
+                .find(|m| m.name() == mode_name)
+                .ok_or_else(|| format!("[keys]: unknown mode '{}'", mode_name))?;
+            for (action_name, keys) in actions {
+                let binding = DEFAULT_BINDINGS
+                    .iter()
+                    .find(|b| b.mode == *mode && b.name == action_name)
+                    .ok_or_else(|| format!("[keys.{}]: unknown action '{}'", mode_name, action_name))?;
+                let keys = keys
+                    .iter()
+                    .map(|k| KeyChord::parse(k).map_err(|e| format!("[keys.{}] {}: {}", mode_name, action_name, e)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                chords.insert((*mode, binding.action), keys);
+            }
+        }
+
+        // a key can do one thing per mode, global keys can't be reused by any mode
+        let mut keys: HashMap<(Mode, KeyChord), KeyAction> = HashMap::new();
+        for b in DEFAULT_BINDINGS {
+            for chord in &chords[&(b.mode, b.action)] {
+                let conflict = if b.mode == Mode::Global {
+                    keys.iter().find(|((_, c), _)| c == chord).map(|((m, _), a)| (*m, *a))
+                } else {
+                    keys.get(&(b.mode, *chord))
+                        .map(|a| (b.mode, *a))
+                        .or_else(|| keys.get(&(Mode::Global, *chord)).map(|a| (Mode::Global, *a)))
+                };
+                if let Some((mode, action)) = conflict {
+                    return Err(format!(
+                        "[keys]: '{}' is bound to both {}.{} and {}.{}",
+                        chord.format(),
+                        mode.name(),
+                        binding_name(mode, action),
+                        b.mode.name(),
+                        b.name
+                    ));
+                }
+                keys.insert((b.mode, *chord), b.action);
+            }
+        }
+        Ok(Keymap { keys, chords })
+    }
+
+    pub fn action(&self, mode: Mode, event: &KeyEvent) -> Option<KeyAction> {
+        self.keys.get(&(mode, KeyChord::from_event(event))).copied()
+    }
+
+    // the first plain character bound to the action, shown underlined in the menus
+    pub fn key_char(&self, mode: Mode, action: KeyAction) -> Option<char> {
+        self.chords.get(&(mode, action))?.iter().find_map(|c| match c.code {
+            KeyCode::Char(x) if c.modifiers.is_empty() => Some(x),
+            _ => None,
+        })
+    }
+
+    // the binding a plain character already has in the mode, global ones included
+    pub fn char_binding(&self, mode: Mode, c: char) -> Option<(Mode, &'static str)> {
+        let chord = KeyChord {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::NONE,
+        };
+        [mode, Mode::Global]
+            .into_iter()
+            .find_map(|m| self.keys.get(&(m, chord)).map(|a| (m, binding_name(m, *a))))
+    }
+
+    // [[actions]] and [[file-actions]] shortcuts only work where the keymap leaves their first char free
+    pub fn check_shortcuts(&self, mode: Mode, section: &str, actions: &[Action]) -> Result<(), String> {
+        for action in actions {
+            let binding = action.shortcut.chars().next().and_then(|c| self.char_binding(mode, c));
+            if let Some((m, name)) = binding {
+                return Err(format!(
+                    "[[{}]] '{}': shortcut '{}' is bound to {}.{}",
+                    section,
+                    action.description,
+                    action.shortcut,
+                    m.name(),
+                    name
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // (keys, description) of every binding in the mode, for the help screen
+    pub fn help(&self, mode: Mode) -> Vec<(String, &'static str)> {
+        self.bindings(mode, true)
+    }
+
+    // same for the popup menus, which don't list how to close them
+    pub fn menu(&self, mode: Mode) -> Vec<(String, &'static str)> {
+        self.bindings(mode, false)
+    }
+
+    // the first key of every menu entry, in menu order, which is what clicking the entry sends
+    pub fn menu_events(&self, mode: Mode) -> Vec<Option<KeyEvent>> {
+        DEFAULT_BINDINGS
+            .iter()
+            .filter(|b| b.mode == mode && b.action != KeyAction::Back)
+            .map(|b| self.chords[&(b.mode, b.action)].first().map(|c| KeyEvent::new(c.code, c.modifiers)))
+            .collect()
+    }
+
+    fn bindings(&self, mode: Mode, with_back: bool) -> Vec<(String, &'static str)> {
+        DEFAULT_BINDINGS
+            .iter()
+            .filter(|b| b.mode == mode && (with_back || b.action != KeyAction::Back))
+            .map(|b| {
+                let keys: Vec<String> = self.chords[&(b.mode, b.action)].iter().map(|c| c.format()).collect();
+                (keys.join(" / "), b.description)
+            })
+            .collect()
+    }
+}
+
+fn binding_name(mode: Mode, action: KeyAction) -> &'static str {
+    DEFAULT_BINDINGS
+        .iter()
+        .find(|b| b.mode == mode && b.action == action)
+        .map_or("", |b| b.name)
+}
@@ -1,40 +1,135 @@
+mod columns;
 mod command_processor;
 mod config;
+mod events;
 mod icons;
+mod keymap;
+mod query;
+mod settings;
 mod torrent_stats;
 mod transmission;
 mod ui;
 mod utils;
 
 use binary_heap_plus::BinaryHeap;
-use command_processor::{TorrentCmd, TorrentUpdate};
+use columns::Column;
+use command_processor::{CmdId, Operation, TorrentCmd, TorrentUpdate, TrackerCmd};
+use keymap::{KeyAction, Keymap, Mode};
+use query::Query;
 use config::{Config, TrafficMonitorOptions, Styles, compute_styles};
+use events::{EventKind, EventLog};
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture, KeyCode},
+    event::{DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::{collections::HashMap, io};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    io,
+};
 use tokio::sync::mpsc::{Receiver, Sender};
-use torrent_stats::{update_torrent_stats, TorrentGroupStats};
-use transmission::{SessionStats, TorrentDetails, TorrentInfo, TorrentStatus};
+use torrent_stats::{folder_rows, is_in_folder, is_recently_finished, update_torrent_stats, FolderRow, TorrentGroupStats};
+use settings::{edit_value, parse_value, toggle_value, Setting, SettingKind, SESSION_SETTINGS, TORRENT_SETTINGS};
+use transmission::{
+    DetailsTab, Peer, Session, SessionStats, TorrentDetails, TorrentInfo, TorrentSet, TorrentStatus, Tracker, DETAILS_TABS,
+};
 use tui::{
     backend::{Backend, CrosstermBackend},
+    layout::Rect,
     widgets::{ListState, TableState},
     Terminal,
 };
 use tui_tree_widget::{flatten, get_identifier_without_leaf, TreeItem, TreeState};
-use utils::{build_file_tree, build_file_tree_index, find_file_position, process_folder, FileIdx};
+use utils::{
+    build_file_nodes, file_tree_folders, file_tree_identifiers, file_tree_index, file_tree_items, filter_file_nodes, find_file_indices,
+    find_file_position, find_node_path, format_status_text, is_torrent_link, process_folder, read_torrent_file,
+    sort_file_nodes, tracker_origin, unix_now, FileIdx, FileNode, FileSort,
+};
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Filter {
     ByStatus(TorrentStatus),
     ByDirectory(String),
+    ByLabel(String),
     Recent,
     Active,
     All,
     Search(String),
     Error,
+    Stalled,
+    Finished, // within `finished-days`
+    Incomplete,
+    Private,
+    AwaitingMetadata,
+    View(usize), // index into config.views
+}
+
+// the built-in filters come first in the sidebar, folders after a separator. Their keys are in the keymap
+pub const NUM_BUILTIN: usize = 16;
+pub const FOLDERS_START: usize = NUM_BUILTIN + 1;
+
+pub fn builtin_filter(i: usize) -> Option<Filter> {
+    let filter = match i {
+        0 => Filter::Recent,
+        1 => Filter::Active,
+        2 => Filter::ByStatus(TorrentStatus::Paused),
+        3 => Filter::ByStatus(TorrentStatus::VerifyQueued),
+        4 => Filter::ByStatus(TorrentStatus::Verifying),
+        5 => Filter::ByStatus(TorrentStatus::DownQueued),
+        6 => Filter::ByStatus(TorrentStatus::Downloading),
+        7 => Filter::ByStatus(TorrentStatus::SeedQueued),
+        8 => Filter::ByStatus(TorrentStatus::Seeding),
+        9 => Filter::Error,
+        10 => Filter::Stalled,
+        11 => Filter::Finished,
+        12 => Filter::Incomplete,
+        13 => Filter::Private,
+        14 => Filter::AwaitingMetadata,
+        15 => Filter::All,
+        _ => return None,
+    };
+    Some(filter)
+}
+
+// the sidebar selections stack, one filter of each facet at a time
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Facet {
+    Status,
+    Folder, // folders and labels
+    View,
+    Search,
+}
+
+impl Filter {
+    pub fn facet(&self) -> Facet {
+        match self {
+            Filter::ByDirectory(_) | Filter::ByLabel(_) => Facet::Folder,
+            Filter::View(_) => Facet::View,
+            Filter::Search(_) => Facet::Search,
+            _ => Facet::Status,
+        }
+    }
+
+    // chips above the main table
+    pub fn chip(&self, app: &App) -> String {
+        match self {
+            Filter::ByStatus(status) => format_status_text(status).to_string(),
+            Filter::ByDirectory(dir) => process_folder(dir, &app.config.connections[app.connection_idx].download_dir),
+            Filter::ByLabel(label) => format!("#{}", label),
+            Filter::Recent => "Recent".to_string(),
+            Filter::Active => "Active".to_string(),
+            Filter::All => "All".to_string(),
+            Filter::Search(text) => format!("\"{}\"", text),
+            Filter::Error => "Error".to_string(),
+            Filter::Stalled => "Stalled".to_string(),
+            Filter::Finished => format!("Finished in {}d", app.config.finished_days),
+            Filter::Incomplete => "Incomplete".to_string(),
+            Filter::Private => "Private".to_string(),
+            Filter::AwaitingMetadata => "Awaiting metadata".to_string(),
+            Filter::View(i) => app.config.views[*i].name.clone(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -51,12 +146,108 @@ pub enum Transition {
     ChooseSortFunc,
     Connection,
     FileAction,
+    AddTorrent,
+    AddTorrentDir,
+    Settings,
+    EditSetting,
+    EditLabels,
+    Trackers,
+    TrackerInput(TrackerEdit),
+    Peers,
+    RenameFile,
+    General,
+    Pieces,
+    FileFilter,
+    Log,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TrackerEdit {
+    Add,
+    Edit(u64),
+    ReplaceFrom,
+    ReplaceTo(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PeerColumn {
+    Address,
+    Client,
+    Progress,
+    Download,
+    Upload,
+}
+
+impl PeerColumn {
+    pub fn next(self) -> PeerColumn {
+        match self {
+            PeerColumn::Address => PeerColumn::Client,
+            PeerColumn::Client => PeerColumn::Progress,
+            PeerColumn::Progress => PeerColumn::Download,
+            PeerColumn::Download => PeerColumn::Upload,
+            PeerColumn::Upload => PeerColumn::Address,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PeerColumn::Address => "Address",
+            PeerColumn::Client => "Client",
+            PeerColumn::Progress => "Progress",
+            PeerColumn::Download => "Down",
+            PeerColumn::Upload => "Up",
+        }
+    }
+}
+
+// what the settings screen edits: daemon's session or the selected torrents
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingsTarget {
+    Session,
+    Torrents(Vec<i64>),
+}
+
+impl SettingsTarget {
+    pub fn settings(&self) -> &'static [Setting] {
+        match self {
+            SettingsTarget::Session => SESSION_SETTINGS,
+            SettingsTarget::Torrents(_) => TORRENT_SETTINGS,
+        }
+    }
 }
 
 impl Transition {
     pub fn is_find(&self) -> bool {
         matches!(self, Transition::Find(_, _))
     }
+
+    // text input modes, where every printable key goes into `App.input`
+    pub fn is_input(&self) -> bool {
+        matches!(
+            self,
+            Transition::Search
+                | Transition::AddTorrent
+                | Transition::EditSetting
+                | Transition::EditLabels
+                | Transition::TrackerInput(_)
+                | Transition::RenameFile
+                | Transition::FileFilter
+        ) || self.is_find()
+    }
+
+    // the details tab a screen belongs to, menus and inputs included
+    pub fn details_tab(&self) -> Option<DetailsTab> {
+        match self {
+            Transition::General => Some(DetailsTab::General),
+            Transition::Files | Transition::FileAction | Transition::RenameFile | Transition::FileFilter => {
+                Some(DetailsTab::Files)
+            }
+            Transition::Peers => Some(DetailsTab::Peers),
+            Transition::Trackers | Transition::TrackerInput(_) => Some(DetailsTab::Trackers),
+            Transition::Pieces => Some(DetailsTab::Pieces),
+            _ => None,
+        }
+    }
 }
 
 pub fn calculate_folder_keys(app: &mut App, skip_folder: Option<String>) {
@@ -85,25 +276,214 @@ pub fn calculate_folder_keys(app: &mut App, skip_folder: Option<String>) {
     app.folder_mapping = mappings;
 }
 
+// the folder tree in the filter menu: the first free char of the shown name, the keymap's keys are taken
+pub fn calculate_folder_tree_keys(app: &mut App) {
+    let mut mappings: Vec<(String, char, usize)> = vec![];
+    for row in app.folder_rows() {
+        let key = row
+            .name
+            .chars()
+            .enumerate()
+            .find(|x| x.1 != '/' && !is_filter_key(&app.keymap, x.1) && !mappings.iter().any(|y| y.1 == x.1));
+        if let Some((i, c)) = key {
+            mappings.push((row.path, c, i));
+        }
+    }
+    app.folder_mapping = mappings;
+}
+
+// opens or closes a folder of the sidebar, the keys move along with the rows
+fn set_folder_expanded(app: &mut App, path: &str, expanded: bool) {
+    if expanded {
+        app.expanded_folders.insert(path.to_string());
+    } else {
+        app.expanded_folders.remove(path);
+    }
+    calculate_folder_tree_keys(app);
+    calculate_view_keys(app);
+    calculate_label_keys(app);
+}
+
+// bound in the filter menu or globally
+fn is_filter_key(keymap: &Keymap, c: char) -> bool {
+    keymap.char_binding(Mode::Filter, c).is_some()
+}
+
+// saved views get the first free char of their name not taken by folders or the keymap
+pub fn calculate_view_keys(app: &mut App) {
+    let mut mappings: Vec<(String, char, usize)> = vec![];
+    for view in &app.config.views {
+        let key = view.name.chars().enumerate().find(|x| {
+            !is_filter_key(&app.keymap, x.1)
+                && !app.folder_mapping.iter().any(|y| y.1 == x.1)
+                && !mappings.iter().any(|y| y.1 == x.1)
+        });
+        if let Some((i, c)) = key {
+            mappings.push((view.name.clone(), c, i));
+        }
+    }
+    app.view_mapping = mappings;
+}
+
+// labels get the first free char not taken by folders, views or the keymap, or no key at all
+pub fn calculate_label_keys(app: &mut App) {
+    let mut labels: Vec<&String> = app.groups.labels.keys().collect();
+    labels.sort();
+
+    let mut mappings: Vec<(String, char, usize)> = vec![];
+    for label in labels {
+        let key = label.chars().enumerate().find(|x| {
+            !is_filter_key(&app.keymap, x.1)
+                && !app.folder_mapping.iter().any(|y| y.1 == x.1)
+                && !app.view_mapping.iter().any(|y| y.1 == x.1)
+                && !mappings.iter().any(|y| y.1 == x.1)
+        });
+        if let Some((i, c)) = key {
+            mappings.push((label.to_string(), c, i));
+        }
+    }
+    app.label_mapping = mappings;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SortKey {
+    DateAdded,
+    Name,
+    Size,
+    Progress,
+    Eta,
+    Ratio,
+    Uploaded,
+    UploadSpeed,
+    DownloadSpeed,
+    Queue,
+    Status,
+    Directory,
+}
+
+impl SortKey {
+    pub fn name(self) -> &'static str {
+        match self {
+            SortKey::DateAdded => "date added",
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Progress => "progress",
+            SortKey::Eta => "eta",
+            SortKey::Ratio => "ratio",
+            SortKey::Uploaded => "uploaded",
+            SortKey::UploadSpeed => "upload speed",
+            SortKey::DownloadSpeed => "download speed",
+            SortKey::Queue => "queue position",
+            SortKey::Status => "status",
+            SortKey::Directory => "directory",
+        }
+    }
+
+    // big numbers and new torrents go on top, names and queue positions read top to bottom
+    fn descending(self) -> bool {
+        !matches!(
+            self,
+            SortKey::Name | SortKey::Eta | SortKey::Queue | SortKey::Status | SortKey::Directory
+        )
+    }
+
+    fn compare(self, a: &TorrentInfo, b: &TorrentInfo) -> Ordering {
+        match self {
+            SortKey::DateAdded => a.added_date.cmp(&b.added_date),
+            SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SortKey::Size => a.size_when_done.cmp(&b.size_when_done),
+            SortKey::Progress => a.percent_done.total_cmp(&b.percent_done),
+            // unknown eta is negative, it goes last
+            SortKey::Eta => {
+                let eta = |x: &TorrentInfo| if x.eta < 0 { i64::MAX } else { x.eta };
+                eta(a).cmp(&eta(b))
+            }
+            SortKey::Ratio => a.upload_ratio.total_cmp(&b.upload_ratio),
+            SortKey::Uploaded => a.uploaded_ever.cmp(&b.uploaded_ever),
+            SortKey::UploadSpeed => a.rate_upload.cmp(&b.rate_upload),
+            SortKey::DownloadSpeed => a.rate_download.cmp(&b.rate_download),
+            SortKey::Queue => a.queue_position.cmp(&b.queue_position),
+            SortKey::Status => (a.status.clone() as i64).cmp(&(b.status.clone() as i64)),
+            SortKey::Directory => a.download_dir.cmp(&b.download_dir),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct SortFunction {
-    pub name: String,
-    pub func: fn(&mut [TorrentInfo]) -> (),
+    pub key: SortKey,
+    pub descending: bool,
+    pub then: Option<SortKey>, // ties are broken in the key's own direction
 }
 
-fn by_date_added(xs: &mut [TorrentInfo]) {
-    xs.sort_unstable_by_key(|x| -x.added_date);
+impl Default for SortFunction {
+    fn default() -> Self {
+        SortFunction::new(SortKey::DateAdded)
+    }
 }
 
-fn by_size(xs: &mut [TorrentInfo]) {
-    xs.sort_unstable_by_key(|x| -x.size_when_done);
+impl SortFunction {
+    pub fn new(key: SortKey) -> Self {
+        SortFunction {
+            key,
+            descending: key.descending(),
+            then: None,
+        }
+    }
+
+    pub fn sort(&self, xs: &mut [TorrentInfo]) {
+        let ordered = |key: SortKey, descending: bool, a: &TorrentInfo, b: &TorrentInfo| {
+            if descending {
+                key.compare(b, a)
+            } else {
+                key.compare(a, b)
+            }
+        };
+        xs.sort_by(|a, b| {
+            let first = ordered(self.key, self.descending, a, b);
+            match self.then {
+                Some(then) => first.then_with(|| ordered(then, then.descending(), a, b)),
+                None => first,
+            }
+        });
+    }
+
+    pub fn name(&self) -> String {
+        let arrow = |descending: bool| if descending { "↓" } else { "↑" };
+        let mut name = format!("by {} {}", self.key.name(), arrow(self.descending));
+        if let Some(then) = self.then {
+            name.push_str(&format!(", then {} {}", then.name(), arrow(then.descending())));
+        }
+        name
+    }
 }
 
-fn by_ratio(xs: &mut [TorrentInfo]) {
-    xs.sort_unstable_by_key(|x| (-x.upload_ratio * 1000.0) as i64);
+// clicking on a header of the main table
+fn sort_by_column(column: Column) -> Option<SortKey> {
+    match column {
+        Column::Status | Column::StatusText => Some(SortKey::Status),
+        Column::Name => Some(SortKey::Name),
+        Column::Done => Some(SortKey::Progress),
+        Column::Eta => Some(SortKey::Eta),
+        Column::Size => Some(SortKey::Size),
+        Column::Up => Some(SortKey::UploadSpeed),
+        Column::Down => Some(SortKey::DownloadSpeed),
+        Column::Uploaded => Some(SortKey::Uploaded),
+        Column::Ratio => Some(SortKey::Ratio),
+        Column::Queue => Some(SortKey::Queue),
+        Column::Added => Some(SortKey::DateAdded),
+        Column::DownloadDir => Some(SortKey::Directory),
+        _ => None,
+    }
 }
 
-fn by_uploaded(xs: &mut [TorrentInfo]) {
-    xs.sort_unstable_by_key(|x| -x.uploaded_ever);
+// the sort belongs to the most specific filter, searches share one whatever the text
+fn sort_slot(filters: &[Filter]) -> Filter {
+    match filters.last() {
+        Some(Filter::Search(_)) => Filter::Search(String::new()),
+        Some(x) => x.clone(),
+        None => Filter::All,
+    }
 }
 
 pub struct App<'a> {
@@ -117,8 +497,15 @@ pub struct App<'a> {
     pub stats: SessionStats,
     pub groups: TorrentGroupStats,
     pub selected: Option<TorrentInfo>,
+    pub marked: HashSet<i64>,
+    pub visual_start: Option<i64>, // torrent id where the visual range starts
     pub folder_mapping: Vec<(String, char, usize)>,
-    pub current_filter: Filter,
+    pub expanded_folders: HashSet<String>, // paths of the open folders in the sidebar
+    pub label_mapping: Vec<(String, char, usize)>,
+    pub view_mapping: Vec<(String, char, usize)>,
+    pub views: Vec<Query>, // compiled config.views
+    pub filters: Vec<Filter>, // ordered by facet, All is the empty list
+    pub active_ids: HashSet<i64>,
     pub upload_data: Vec<u64>,
     pub num_active: usize,
     pub input: String,
@@ -127,13 +514,66 @@ pub struct App<'a> {
     pub tree_items: Vec<TreeItem<'a>>,
     pub tree_index: Vec<FileIdx>,
     pub config: Config,
-    pub err: Option<(String, String)>,
-    pub sort_func: SortFunction,
+    pub events: EventLog, // outlives reconnects
+    pub connection_lost: bool, // the daemon stopped answering, the next update means it's back
+    pub log_state: TableState,
+    pub pending: BTreeMap<CmdId, (Operation, Vec<i64>)>, // sent, not acknowledged yet
+    pub last_cmd_id: CmdId,
+    pub ui_ticks: usize, // drives the spinner
+    pub add_paused: bool,
+    pub session: Option<Session>,
+    pub settings_target: SettingsTarget,
+    pub settings_state: TableState,
+    pub torrent_settings: serde_json::Map<String, serde_json::Value>,
+    pub settings_changed: serde_json::Map<String, serde_json::Value>,
+    pub trackers_state: TableState,
+    pub peers_state: TableState,
+    pub peers_sort: PeerColumn,
+    pub peers_sort_asc: bool,
+    pub details_tab: DetailsTab, // the last one opened
+    pub details_pane: bool,      // as last told to the processor
+    pub file_sort: FileSort,
+    pub file_sort_reverse: bool,
+    pub file_filter: String, // lowercase
+    pub input_error: Option<String>,
+    pub sorts: HashMap<Filter, SortFunction>, // remembered per filter
+    pub sort_then: bool,                      // the sort menu picks the secondary key
     pub connection_idx: usize,
+    pub keymap: Keymap,
+    pub areas: Areas,
     pub styles: Styles
 }
 
+// where things were drawn last time, to find out what a mouse click hits
+#[derive(Debug, Clone, Default)]
+pub struct Areas {
+    pub table: Option<Rect>, // inside the borders, the header is the first line
+    pub table_columns: Vec<(Column, u16, u16)>, // column, x, width
+    pub table_offset: usize,
+    pub filters: Option<Rect>,
+    pub filters_offset: usize,
+    pub tree: Option<Rect>,
+    pub tree_offset: usize,
+    pub chips: Vec<(Facet, Rect)>,
+    pub details_pane: Option<Rect>,
+    pub menu: Vec<(Rect, KeyEvent)>, // entries of the open menu or dialog and the key each one stands for
+}
+
 impl App<'_> {
+    pub fn sort_func(&self) -> SortFunction {
+        self.sorts.get(&sort_slot(&self.filters)).copied().unwrap_or_default()
+    }
+
+    fn set_sort_func(&mut self, sort_func: SortFunction) {
+        self.sorts.insert(sort_slot(&self.filters), sort_func);
+        sort_func.sort(&mut self.filtered_torrents);
+    }
+
+    // the visible part of the folder tree in the sidebar
+    pub fn folder_rows(&self) -> Vec<FolderRow> {
+        folder_rows(&self.groups.folder_tree, &self.expanded_folders)
+    }
+
     fn reset(&mut self) {
         self.transition = Transition::MainScreen;
         self.prev_transition = Transition::MainScreen;
@@ -145,7 +585,13 @@ impl App<'_> {
         self.stats = SessionStats::empty();
         self.groups = TorrentGroupStats::empty();
         self.selected = None;
+        self.marked = HashSet::new();
+        self.visual_start = None;
+        self.connection_lost = false;
         self.folder_mapping = vec![];
+        self.expanded_folders = HashSet::new();
+        self.label_mapping = vec![];
+        self.view_mapping = vec![];
         self.upload_data = vec![];
         self.num_active = 0;
         self.input = "".to_string();
@@ -153,12 +599,19 @@ impl App<'_> {
         self.tree_index = vec![];
         self.details = None;
         self.tree_items = vec![];
-        self.err = None;
+        self.session = None;
+        self.settings_target = SettingsTarget::Session;
+        self.settings_state = TableState::default();
+        self.torrent_settings = serde_json::Map::new();
+        self.settings_changed = serde_json::Map::new();
+        self.trackers_state = TableState::default();
+        self.peers_state = TableState::default();
+        self.input_error = None;
     }
 }
 
 impl App<'_> {
-    fn new(config: Config) -> Self {
+    fn new(config: Config, keymap: Keymap, views: Vec<Query>) -> Self {
         let left_filter_state = ListState::default();
         let main_table_state = TableState::default();
         let torrents: HashMap<i64, TorrentInfo> = HashMap::new();
@@ -179,8 +632,15 @@ impl App<'_> {
             stats,
             groups,
             selected: None,
+            marked: HashSet::new(),
+            visual_start: None,
             folder_mapping: vec![],
-            current_filter: Filter::Recent,
+            expanded_folders: HashSet::new(),
+            label_mapping: vec![],
+            view_mapping: vec![],
+            views,
+            filters: vec![Filter::Recent],
+            active_ids: HashSet::new(),
             upload_data: vec![],
             num_active: 0,
             input: "".to_string(),
@@ -189,19 +649,38 @@ impl App<'_> {
             details: None,
             tree_items: vec![],
             config,
-            err: None,
-            sort_func: SortFunction {
-                name: String::from("Date Added"),
-                func: by_date_added,
-            },
+            events: EventLog::default(),
+            connection_lost: false,
+            log_state: TableState::default(),
+            pending: BTreeMap::new(),
+            last_cmd_id: 0,
+            ui_ticks: 0,
+            add_paused: false,
+            session: None,
+            settings_target: SettingsTarget::Session,
+            settings_state: TableState::default(),
+            torrent_settings: serde_json::Map::new(),
+            settings_changed: serde_json::Map::new(),
+            trackers_state: TableState::default(),
+            peers_state: TableState::default(),
+            peers_sort: PeerColumn::Download,
+            peers_sort_asc: false,
+            details_tab: DetailsTab::default(),
+            details_pane: false,
+            file_sort: FileSort::default(),
+            file_sort_reverse: false,
+            file_filter: String::new(),
+            input_error: None,
+            sorts: HashMap::new(),
+            sort_then: false,
             connection_idx: 0,
+            keymap,
+            areas: Areas::default(),
             styles
         }
     }
 }
 
-// TODO: min size 67 x 20, then super-min-size.
-// then we need name, size, done, up, down
 fn run_app<B: Backend>(
     terminal: &mut Terminal<B>,
     mut app: App,
@@ -210,165 +689,166 @@ fn run_app<B: Backend>(
 ) -> io::Result<()> {
     loop {
         terminal.draw(|f| ui::ui(f, &mut app))?;
+        // the pane needs more fields than the main screen
+        let pane = app.areas.details_pane.is_some();
+        if pane != app.details_pane {
+            app.details_pane = pane;
+            let _ = sender.blocking_send(TorrentCmd::DetailsPane(pane));
+        }
 
-        match rx.blocking_recv() {
-            Some(TorrentUpdate::UiTick) => {}
-            Some(TorrentUpdate::Err { msg, details }) => {
-                if app.err.is_none() {
-                    // FIXME: poor keep first error, may be bad in general, but should do for now
-                    app.err = Some((msg, details));
-                }
+        // clicks on menus and dialogs come back as their keys
+        let update = match rx.blocking_recv() {
+            Some(TorrentUpdate::Mouse(event)) => handle_mouse(&mut app, event, &sender).map(TorrentUpdate::Input),
+            x => x,
+        };
+        match update {
+            Some(TorrentUpdate::UiTick) => {
+                app.ui_ticks = app.ui_ticks.wrapping_add(1);
             }
-            Some(TorrentUpdate::Input(event)) => match event.code {
-                KeyCode::Char('q') => {
+            Some(TorrentUpdate::Err { msg, details, connection }) => {
+                app.connection_lost |= connection;
+                app.events.push(EventKind::Error, msg, details);
+            }
+            Some(TorrentUpdate::Mouse(_)) => {}
+            Some(TorrentUpdate::Input(event)) => match app.keymap.action(Mode::Global, &event) {
+                Some(KeyAction::Quit) if !app.transition.is_input() => {
                     //let _ = sender.blocking_send(TorrentCmd::PoisonPill);
                     break Ok(());
                 }
+                Some(KeyAction::Help) if !app.transition.is_input() => {
+                    if app.transition == Transition::Help {
+                        app.transition = app.prev_transition.clone();
+                    } else {
+                        app.prev_transition = app.transition.clone();
+                        app.transition = Transition::Help;
+                    }
+                }
+                _ if details_tab_key(&app, &event).is_some() => {
+                    if let Some(tab) = details_tab_key(&app, &event) {
+                        open_tab(&mut app, tab, &sender);
+                    }
+                }
                 _ => {
                     match app.transition {
-                        Transition::MainScreen => match event.code {
-                            KeyCode::Char(' ') => {
+                        Transition::MainScreen => match app.keymap.action(Mode::Main, &event) {
+                            Some(KeyAction::ActionMenu) => {
                                 if app.selected.is_some() {
+                                    commit_visual_range(&mut app);
                                     app.transition = Transition::Action;
                                 }
                             }
-                            KeyCode::F(1) => {
-                                app.prev_transition = app.transition;
-                                app.transition = Transition::Help;
-                            }
-                            KeyCode::Char('c') => {
-                                app.transition = Transition::Connection;
-                            }
-                            KeyCode::Down | KeyCode::Char('j') => {
-                                if let Some(selected) = app.main_table_state.selected() {
-                                    let amount_pets = app.filtered_torrents.len();
-                                    if selected >= amount_pets - 1 {
-                                        select_first_torrent(&mut app, sender.clone());
-                                    } else {
-                                        app.main_table_state.select(Some(selected + 1));
-                                        app.selected = Some(app.filtered_torrents[selected + 1].clone());
-                                        sender
-                                            .blocking_send(TorrentCmd::GetDetails(
-                                                app.filtered_torrents[selected + 1].id,
-                                            ))
-                                            .expect("foo");
+                            Some(KeyAction::Mark) => {
+                                if let Some(x) = &app.selected {
+                                    if !app.marked.remove(&x.id) {
+                                        app.marked.insert(x.id);
                                     }
-                                } else {
-                                    select_first_torrent(&mut app, sender.clone());
                                 }
                             }
-                            KeyCode::Up | KeyCode::Char('k') => {
-                                if !app.filtered_torrents.is_empty() {
-                                    if let Some(selected) = app.main_table_state.selected() {
-                                        let selected = selected.min(app.filtered_torrents.len());
-                                        let amount_pets = app.filtered_torrents.len();
-                                        if selected > 0 {
-                                            app.main_table_state.select(Some(selected - 1));
-                                            app.selected = Some(app.filtered_torrents[selected - 1].clone());
-                                            sender
-                                                .blocking_send(TorrentCmd::GetDetails(
-                                                    app.filtered_torrents[selected - 1].id,
-                                                ))
-                                                .expect("foo");
-                                        } else {
-                                            app.main_table_state.select(Some(amount_pets - 1));
-                                            app.selected = Some(app.filtered_torrents[amount_pets - 1].clone());
-                                            sender
-                                                .blocking_send(TorrentCmd::GetDetails(
-                                                    app.filtered_torrents[amount_pets - 1].id,
-                                                ))
-                                                .expect("foo");
-                                        }
-                                    } else {
-                                        select_first_torrent(&mut app, sender.clone());
-                                    }
+                            Some(KeyAction::MarkRange) => {
+                                if app.visual_start.is_some() {
+                                    commit_visual_range(&mut app);
                                 } else {
-                                    app.selected = None;
-                                    let _ = sender.blocking_send(TorrentCmd::Select(None));
+                                    app.visual_start = app.selected.as_ref().map(|x| x.id);
                                 }
                             }
-                            KeyCode::Char('f') => {
-                                calculate_folder_keys(&mut app, None);
+                            Some(KeyAction::UnmarkAll) => {
+                                app.marked.clear();
+                                app.visual_start = None;
+                            }
+                            Some(KeyAction::Connections) => {
+                                app.transition = Transition::Connection;
+                            }
+                            Some(KeyAction::Next) => select_next_torrent(&mut app, &sender),
+                            Some(KeyAction::Prev) => select_prev_torrent(&mut app, &sender),
+                            Some(KeyAction::Filter) => {
+                                calculate_folder_tree_keys(&mut app);
+                                calculate_view_keys(&mut app);
+                                calculate_label_keys(&mut app);
                                 app.transition = Transition::Filter;
                             }
-                            KeyCode::Char('s') => {
+                            Some(KeyAction::Search) => {
+                                app.input_error = Query::parse(&app.input).err();
                                 app.transition = Transition::Search;
                             }
-                            KeyCode::Char('/') => {
+                            Some(KeyAction::FindNext) => {
                                 app.input = "".to_string();
+                                app.input_error = None;
                                 app.transition = Transition::Find(true, app.main_table_state.selected().unwrap_or(0));
                             }
-                            KeyCode::Char('?') => {
+                            Some(KeyAction::FindPrev) => {
                                 app.input = "".to_string();
+                                app.input_error = None;
                                 app.transition = Transition::Find(false, app.main_table_state.selected().unwrap_or(0));
                             }
-                            KeyCode::Char('d') => {
-                                app.transition = Transition::Files;
+                            Some(KeyAction::Details) => {
                                 app.tree_state = TreeState::default();
                                 open_first_level(&mut app);
                                 move_up_down(&mut app, true);
+                                let tab = app.details_tab;
+                                open_tab(&mut app, tab, &sender);
                             }
-                            KeyCode::Char('S') => {
+                            Some(KeyAction::Sort) => {
                                 app.transition = Transition::ChooseSortFunc;
                             }
-                            KeyCode::Esc => {
-                                if let Filter::Search(_) = app.current_filter {
-                                    app.current_filter = Filter::Recent;
-                                }
+                            Some(KeyAction::AddTorrent) => {
+                                app.input = "".to_string();
+                                app.transition = Transition::AddTorrent;
+                            }
+                            Some(KeyAction::SessionSettings) => {
+                                let _ = sender.blocking_send(TorrentCmd::GetSession);
+                                app.settings_target = SettingsTarget::Session;
+                                app.settings_state = TableState::default();
+                                move_setting(&mut app, true);
+                                app.transition = Transition::Settings;
+                            }
+                            Some(KeyAction::EventLog) => {
+                                app.events.dismiss_toasts();
+                                app.log_state.select(if app.events.is_empty() { None } else { Some(0) });
+                                app.transition = Transition::Log;
+                            }
+                            Some(KeyAction::Back) if !app.events.toasts(unix_now()).is_empty() => {
+                                app.events.dismiss_toasts();
+                            }
+                            Some(KeyAction::Back) if app.visual_start.is_some() => app.visual_start = None,
+                            Some(KeyAction::Back) => {
+                                remove_facet(&mut app, Facet::Search);
+                                apply_filters(&mut app);
                             }
                             _ => {}
                         },
-                        Transition::Action => match event.code {
-                            KeyCode::Char(' ') | KeyCode::Esc => {
+                        Transition::Action => match app.keymap.action(Mode::Action, &event) {
+                            Some(KeyAction::Back) => {
                                 app.transition = Transition::MainScreen;
                             }
-                            KeyCode::Char('s') => {
-                                if let Some(x) = app
-                                    .main_table_state
-                                    .selected()
-                                    .and_then(|x| app.filtered_torrents.get(x))
-                                {
-                                    sender
-                                        .blocking_send(TorrentCmd::Start(vec![x.id]))
-                                        .expect("should send");
+                            Some(KeyAction::Start) => {
+                                let ids = target_ids(&app);
+                                if !ids.is_empty() {
+                                    send_cmd(&mut app, &sender, TorrentCmd::Start(ids));
                                 }
                                 app.transition = Transition::MainScreen;
                             }
-                            KeyCode::Char('S') => {
-                                if let Some(x) = app
-                                    .main_table_state
-                                    .selected()
-                                    .and_then(|x| app.filtered_torrents.get(x))
-                                {
-                                    sender
-                                        .blocking_send(TorrentCmd::StartNow(vec![x.id]))
-                                        .expect("should send");
+                            Some(KeyAction::StartNow) => {
+                                let ids = target_ids(&app);
+                                if !ids.is_empty() {
+                                    send_cmd(&mut app, &sender, TorrentCmd::StartNow(ids));
                                 }
                                 app.transition = Transition::MainScreen;
                             }
-                            KeyCode::Char('p') => {
-                                if let Some(x) = app
-                                    .main_table_state
-                                    .selected()
-                                    .and_then(|x| app.filtered_torrents.get(x))
-                                {
-                                    sender.blocking_send(TorrentCmd::Stop(vec![x.id])).expect("should send");
+                            Some(KeyAction::Stop) => {
+                                let ids = target_ids(&app);
+                                if !ids.is_empty() {
+                                    send_cmd(&mut app, &sender, TorrentCmd::Stop(ids));
                                 }
                                 app.transition = Transition::MainScreen;
                             }
-                            KeyCode::Char('v') => {
-                                if let Some(x) = app
-                                    .main_table_state
-                                    .selected()
-                                    .and_then(|x| app.filtered_torrents.get(x))
-                                {
-                                    sender
-                                        .blocking_send(TorrentCmd::Verify(vec![x.id]))
-                                        .expect("should send");
+                            Some(KeyAction::Verify) => {
+                                let ids = target_ids(&app);
+                                if !ids.is_empty() {
+                                    send_cmd(&mut app, &sender, TorrentCmd::Verify(ids));
                                 }
                                 app.transition = Transition::MainScreen;
                             }
-                            KeyCode::Char('m') => {
+                            Some(KeyAction::Move) => {
                                 if let Some(x) = app
                                     .main_table_state
                                     .selected()
@@ -379,244 +859,120 @@ fn run_app<B: Backend>(
                                     app.transition = Transition::Move;
                                 }
                             }
-                            KeyCode::Char('x') => {
-                                app.transition = Transition::ConfirmRemove(false);
-                            }
-                            KeyCode::Char('X') => {
-                                app.transition = Transition::ConfirmRemove(true);
+                            Some(KeyAction::TorrentSettings) => {
+                                let ids = target_ids(&app);
+                                if let Some(id) = ids.first() {
+                                    // a batch shows the settings of its first torrent
+                                    let _ = sender.blocking_send(TorrentCmd::GetTorrentSettings(*id));
+                                    app.settings_target = SettingsTarget::Torrents(ids);
+                                    app.torrent_settings = serde_json::Map::new();
+                                    app.settings_changed = serde_json::Map::new();
+                                    app.settings_state = TableState::default();
+                                    move_setting(&mut app, true);
+                                    app.transition = Transition::Settings;
+                                }
                             }
-                            KeyCode::Char('k') => {
+                            Some(KeyAction::Labels) => {
                                 if let Some(x) = app
                                     .main_table_state
                                     .selected()
                                     .and_then(|x| app.filtered_torrents.get(x))
                                 {
-                                    sender
-                                        .blocking_send(TorrentCmd::QueueMoveUp(vec![x.id]))
-                                        .expect("should send");
+                                    app.input = x.labels.join(", ");
+                                    app.transition = Transition::EditLabels;
+                                }
+                            }
+                            Some(KeyAction::Remove) => {
+                                app.transition = Transition::ConfirmRemove(false);
+                            }
+                            Some(KeyAction::RemoveWithData) => {
+                                app.transition = Transition::ConfirmRemove(true);
+                            }
+                            Some(KeyAction::QueueUp) => {
+                                let ids = target_ids(&app);
+                                if !ids.is_empty() {
+                                    send_cmd(&mut app, &sender, TorrentCmd::QueueMoveUp(ids));
                                 }
                                 app.transition = Transition::MainScreen;
                             }
-                            KeyCode::Char('j') => {
-                                if let Some(x) = app
-                                    .main_table_state
-                                    .selected()
-                                    .and_then(|x| app.filtered_torrents.get(x))
-                                {
-                                    sender
-                                        .blocking_send(TorrentCmd::QueueMoveDown(vec![x.id]))
-                                        .expect("should send");
+                            Some(KeyAction::QueueDown) => {
+                                let ids = target_ids(&app);
+                                if !ids.is_empty() {
+                                    send_cmd(&mut app, &sender, TorrentCmd::QueueMoveDown(ids));
                                 }
                                 app.transition = Transition::MainScreen;
                             }
-                            KeyCode::Char('K') => {
-                                if let Some(x) = app
-                                    .main_table_state
-                                    .selected()
-                                    .and_then(|x| app.filtered_torrents.get(x))
-                                {
-                                    sender
-                                        .blocking_send(TorrentCmd::QueueMoveTop(vec![x.id]))
-                                        .expect("should send");
+                            Some(KeyAction::QueueTop) => {
+                                let ids = target_ids(&app);
+                                if !ids.is_empty() {
+                                    send_cmd(&mut app, &sender, TorrentCmd::QueueMoveTop(ids));
                                 }
                                 app.transition = Transition::MainScreen;
                             }
-                            KeyCode::Char('J') => {
-                                if let Some(x) = app
-                                    .main_table_state
-                                    .selected()
-                                    .and_then(|x| app.filtered_torrents.get(x))
-                                {
-                                    sender
-                                        .blocking_send(TorrentCmd::QueueMoveBottom(vec![x.id]))
-                                        .expect("should send");
+                            Some(KeyAction::QueueBottom) => {
+                                let ids = target_ids(&app);
+                                if !ids.is_empty() {
+                                    send_cmd(&mut app, &sender, TorrentCmd::QueueMoveBottom(ids));
                                 }
                                 app.transition = Transition::MainScreen;
                             }
-                            KeyCode::Char(c) => {
-                                if let Some(x) = app
+                            None => if let KeyCode::Char(c) = event.code {
+                                let id = app
                                     .main_table_state
                                     .selected()
                                     .and_then(|x| app.filtered_torrents.get(x))
-                                {
-                                    if let Some(idx) = app
-                                        .config
-                                        .actions
-                                        .iter()
-                                        .enumerate()
-                                        .find(|x| x.1.shortcut.starts_with(c))
-                                    {
-                                        sender
-                                            .blocking_send(TorrentCmd::Action(x.id, idx.0))
-                                            .expect("should send");
-                                    }
+                                    .map(|x| x.id);
+                                let idx = app.config.actions.iter().position(|x| x.shortcut.starts_with(c));
+                                if let (Some(id), Some(idx)) = (id, idx) {
+                                    send_cmd(&mut app, &sender, TorrentCmd::Action(id, idx));
                                 }
                                 app.transition = Transition::MainScreen;
                             }
                             _ => {}
                         },
-                        Transition::Filter => match event.code {
-                            KeyCode::Esc => {
+                        Transition::Filter => match app.keymap.action(Mode::Filter, &event) {
+                            Some(KeyAction::Back) => {
                                 app.transition = Transition::MainScreen;
                             }
-                            KeyCode::Char(c) => {
-                                if let Some(x) = app.folder_mapping.iter().find(|x| x.1 == c) {
-                                    app.current_filter = Filter::ByDirectory(x.0.clone());
-                                    let idx = 12
-                                        + app
-                                            .folder_mapping
-                                            .iter()
-                                            .enumerate()
-                                            .find(|y| y.1 .0 == x.0)
-                                            .map(|x| x.0)
-                                            .unwrap_or(0);
-                                    app.left_filter_state.select(Some(idx));
-                                    app.transition = Transition::MainScreen;
-                                    app.filtered_torrents = app
-                                        .torrents
-                                        .values()
-                                        .filter(|y| y.download_dir == x.0)
-                                        .cloned()
-                                        .collect();
-                                    (app.sort_func.func)(&mut app.filtered_torrents);
-                                    select_first_torrent(&mut app, sender.clone());
-                                } else {
-                                    match c {
-                                        'R' => {
-                                            app.current_filter = Filter::Recent;
-                                            app.transition = Transition::MainScreen;
-                                            app.left_filter_state.select(Some(0));
-                                            app.filtered_torrents = most_recent_items(&app.torrents);
-                                            select_first_torrent(&mut app, sender.clone());
-                                        }
-                                        'A' => {
-                                            app.current_filter = Filter::Active;
-                                            app.transition = Transition::MainScreen;
-                                            app.left_filter_state.select(Some(1));
-                                            select_first_torrent(&mut app, sender.clone());
-                                        }
-                                        'P' => {
-                                            app.current_filter = Filter::ByStatus(TorrentStatus::Paused);
-                                            app.transition = Transition::MainScreen;
-                                            app.left_filter_state.select(Some(2));
-                                            app.filtered_torrents = app
-                                                .torrents
-                                                .values()
-                                                .filter(|x| x.status == TorrentStatus::Paused)
-                                                .cloned()
-                                                .collect();
-                                            (app.sort_func.func)(&mut app.filtered_torrents);
-                                            select_first_torrent(&mut app, sender.clone());
-                                        }
-                                        'L' => {
-                                            app.current_filter = Filter::All;
-                                            app.transition = Transition::MainScreen;
-                                            app.left_filter_state.select(Some(10));
-                                            app.filtered_torrents = app.torrents.values().cloned().collect();
-                                            (app.sort_func.func)(&mut app.filtered_torrents);
-                                            select_first_torrent(&mut app, sender.clone());
-                                        }
-                                        'G' => {
-                                            app.current_filter = Filter::ByStatus(TorrentStatus::VerifyQueued);
-                                            app.transition = Transition::MainScreen;
-                                            app.left_filter_state.select(Some(3));
-                                            app.filtered_torrents = app
-                                                .torrents
-                                                .values()
-                                                .filter(|x| x.status == TorrentStatus::VerifyQueued)
-                                                .cloned()
-                                                .collect();
-                                            (app.sort_func.func)(&mut app.filtered_torrents);
-                                            select_first_torrent(&mut app, sender.clone());
-                                        }
-                                        'C' => {
-                                            app.current_filter = Filter::ByStatus(TorrentStatus::Verifying);
-                                            app.transition = Transition::MainScreen;
-                                            app.left_filter_state.select(Some(4));
-                                            app.filtered_torrents = app
-                                                .torrents
-                                                .values()
-                                                .filter(|x| x.status == TorrentStatus::Verifying)
-                                                .cloned()
-                                                .collect();
-                                            (app.sort_func.func)(&mut app.filtered_torrents);
-                                            select_first_torrent(&mut app, sender.clone());
-                                        }
-                                        'Q' => {
-                                            app.current_filter = Filter::ByStatus(TorrentStatus::DownQueued);
-                                            app.transition = Transition::MainScreen;
-                                            app.left_filter_state.select(Some(5));
-                                            app.filtered_torrents = app
-                                                .torrents
-                                                .values()
-                                                .filter(|x| x.status == TorrentStatus::DownQueued)
-                                                .cloned()
-                                                .collect();
-                                            (app.sort_func.func)(&mut app.filtered_torrents);
-                                            select_first_torrent(&mut app, sender.clone());
-                                        }
-                                        'D' => {
-                                            app.current_filter = Filter::ByStatus(TorrentStatus::Downloading);
-                                            app.transition = Transition::MainScreen;
-                                            app.left_filter_state.select(Some(6));
-                                            app.filtered_torrents = app
-                                                .torrents
-                                                .values()
-                                                .filter(|x| x.status == TorrentStatus::Downloading)
-                                                .cloned()
-                                                .collect();
-                                            (app.sort_func.func)(&mut app.filtered_torrents);
-                                            select_first_torrent(&mut app, sender.clone());
-                                        }
-                                        'U' => {
-                                            app.current_filter = Filter::ByStatus(TorrentStatus::SeedQueued);
-                                            app.transition = Transition::MainScreen;
-                                            app.left_filter_state.select(Some(7));
-                                            app.filtered_torrents = app
-                                                .torrents
-                                                .values()
-                                                .filter(|x| x.status == TorrentStatus::SeedQueued)
-                                                .cloned()
-                                                .collect();
-                                            (app.sort_func.func)(&mut app.filtered_torrents);
-                                            select_first_torrent(&mut app, sender.clone());
-                                        }
-                                        'S' => {
-                                            app.current_filter = Filter::ByStatus(TorrentStatus::Seeding);
-                                            app.transition = Transition::MainScreen;
-                                            app.left_filter_state.select(Some(8));
-                                            app.filtered_torrents = app
-                                                .torrents
-                                                .values()
-                                                .filter(|x| x.status == TorrentStatus::Seeding)
-                                                .cloned()
-                                                .collect();
-                                            (app.sort_func.func)(&mut app.filtered_torrents);
-                                            select_first_torrent(&mut app, sender.clone());
-                                        }
-                                        'E' => {
-                                            app.current_filter = Filter::Error;
-                                            app.filtered_torrents =
-                                                app.torrents.values().filter(|x| x.error > 0).cloned().collect();
-                                            app.transition = Transition::MainScreen;
-                                            app.left_filter_state.select(Some(9));
-                                            select_first_torrent(&mut app, sender.clone());
-                                        }
-                                        _ => {}
-                                    }
+                            Some(KeyAction::BuiltinFilter(i)) => {
+                                if let Some(filter) = builtin_filter(i) {
+                                    apply_filter(&mut app, filter, i, &sender);
+                                }
+                            }
+                            // close and open the highlighted folder
+                            Some(action @ (KeyAction::Collapse | KeyAction::Expand)) => {
+                                let row = app
+                                    .left_filter_state
+                                    .selected()
+                                    .and_then(|i| i.checked_sub(FOLDERS_START))
+                                    .and_then(|i| app.folder_rows().get(i).cloned());
+                                if let Some(row) = row.filter(|x| x.has_children) {
+                                    set_folder_expanded(&mut app, &row.path, action == KeyAction::Expand);
+                                }
+                            }
+                            // drops the most specific filter
+                            Some(KeyAction::RemoveFilter) => {
+                                app.filters.pop();
+                                app.transition = Transition::MainScreen;
+                                apply_filters(&mut app);
+                                select_first_torrent(&mut app, sender.clone());
+                            }
+                            None => {
+                                if let KeyCode::Char(c) = event.code {
+                                    apply_filter_key(&mut app, c, &sender);
                                 }
                             }
                             _ => {}
                         },
-                        Transition::Help => match event.code {
-                            KeyCode::F(1) | KeyCode::Esc => {
+                        Transition::Help => {
+                            if event.code == KeyCode::Esc {
                                 app.transition = app.prev_transition.clone();
                             }
-                            _ => {}
-                        },
+                        }
                         Transition::Find(forward, current) => match event.code {
                             KeyCode::Esc => {
                                 app.input = "".to_string();
+                                app.input_error = None;
                                 app.transition = Transition::MainScreen;
                                 app.main_table_state.select(Some(current));
                             }
@@ -625,163 +981,416 @@ fn run_app<B: Backend>(
                                     let x = &app.filtered_torrents[*i];
                                     app.selected = Some(x.clone());
                                     sender.blocking_send(TorrentCmd::GetDetails(x.id)).expect("foo");
+                                    app.input_error = None;
                                     app.transition = Transition::MainScreen;
                                 }
                             }
                             KeyCode::Backspace => {
                                 app.input.pop(); // now we need go back..
-                                let maybe_x = if forward {
-                                    app.filtered_torrents
-                                        .iter()
-                                        .enumerate()
-                                        .skip(current.saturating_sub(1))
-                                        .find(|x| x.1.name.contains(&app.input))
-                                } else {
-                                    app.filtered_torrents
-                                        .iter()
-                                        .enumerate()
-                                        .rev()
-                                        .skip(app.filtered_torrents.len().saturating_sub(current.saturating_add(1)))
-                                        .find(|x| x.1.name.contains(&app.input))
-                                };
-                                if let Some((i, _)) = maybe_x {
-                                    app.main_table_state.select(Some(i));
-                                }
+                                find_torrent(&mut app, forward, current);
                             }
                             KeyCode::Char(c) => {
                                 app.input.push(c);
-                                let maybe_x = if forward {
-                                    app.filtered_torrents
-                                        .iter()
-                                        .enumerate()
-                                        .skip(current.saturating_sub(1))
-                                        .find(|x| x.1.name.contains(&app.input))
-                                } else {
-                                    app.filtered_torrents
-                                        .iter()
-                                        .enumerate()
-                                        .rev()
-                                        .skip(app.filtered_torrents.len().saturating_sub(current.saturating_add(1)))
-                                        .find(|x| x.1.name.contains(&app.input))
-                                };
-                                if let Some((i, _)) = maybe_x {
-                                    app.main_table_state.select(Some(i));
-                                }
+                                find_torrent(&mut app, forward, current);
                             }
                             _ => {}
                         },
                         Transition::Search => match event.code {
                             KeyCode::Esc => {
                                 app.input = "".to_string();
+                                app.input_error = None;
                                 app.transition = Transition::MainScreen;
-                                if let Filter::Search(_) = app.current_filter {
-                                    app.current_filter = Filter::Recent;
-                                };
+                                remove_facet(&mut app, Facet::Search);
+                                apply_filters(&mut app);
                             }
-                            KeyCode::Enter => {
-                                app.current_filter = Filter::Search(app.input.clone());
+                            KeyCode::Enter if app.input_error.is_none() => {
+                                remove_facet(&mut app, Facet::Search);
+                                if !app.input.trim().is_empty() {
+                                    let search = Filter::Search(app.input.clone());
+                                    toggle_filter(&mut app, search);
+                                }
                                 app.transition = Transition::MainScreen;
+                                apply_filters(&mut app);
+                                select_first_torrent(&mut app, sender.clone());
                             }
                             KeyCode::Backspace => {
                                 app.input.pop();
+                                app.input_error = Query::parse(&app.input).err();
+                            }
+                            KeyCode::Char(c) => {
+                                app.input.push(c);
+                                app.input_error = Query::parse(&app.input).err();
                             }
-                            KeyCode::Char(c) => app.input.push(c),
                             _ => {}
                         },
-                        Transition::Move => match event.code {
+                        Transition::AddTorrent => match event.code {
                             KeyCode::Esc => {
+                                app.input = "".to_string();
                                 app.transition = Transition::MainScreen;
                             }
-                            KeyCode::Char(c) => {
-                                if let Some(x) = app
-                                    .main_table_state
-                                    .selected()
-                                    .and_then(|x| app.filtered_torrents.get(x))
-                                {
-                                    if let Some((f, _, _)) = app.folder_mapping.iter().find(|y| y.1 == c) {
-                                        sender
-                                            .blocking_send(TorrentCmd::Move(vec![x.id], f.to_string(), true))
-                                            .expect("should send");
-                                        app.transition = Transition::MainScreen;
-                                    }
-                                    /*if let Some(f) = app.groups.folder_keys.get(&c) {
-                                    }*/
-                                }
+                            KeyCode::Enter if !app.input.trim().is_empty() => {
+                                calculate_folder_keys(&mut app, None);
+                                app.transition = Transition::AddTorrentDir;
+                            }
+                            KeyCode::Backspace => {
+                                app.input.pop();
                             }
+                            KeyCode::Char(c) => app.input.push(c),
                             _ => {}
                         },
-                        Transition::ConfirmRemove(with_data) => match event.code {
-                            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
-                                app.transition = Transition::MainScreen;
+                        Transition::AddTorrentDir => match event.code {
+                            KeyCode::Esc => {
+                                app.transition = Transition::AddTorrent;
                             }
-                            KeyCode::Char('y') => {
-                                if let Some(x) = app
-                                    .main_table_state
-                                    .selected()
-                                    .and_then(|x| app.filtered_torrents.get(x))
-                                {
-                                    sender
-                                        .blocking_send(TorrentCmd::Delete(vec![x.id], with_data))
-                                        .expect("should send");
+                            KeyCode::Tab => {
+                                app.add_paused = !app.add_paused;
+                            }
+                            KeyCode::Enter => {
+                                add_torrent(&mut app, None, &sender);
+                            }
+                            KeyCode::Char(c) => {
+                                if let Some((f, _, _)) = app.folder_mapping.iter().find(|y| y.1 == c) {
+                                    let download_dir = f.to_string();
+                                    add_torrent(&mut app, Some(download_dir), &sender);
                                 }
+                            }
+                            _ => {}
+                        },
+                        Transition::Log => match app.keymap.action(Mode::Log, &event) {
+                            Some(KeyAction::Back) => {
                                 app.transition = Transition::MainScreen;
                             }
+                            Some(KeyAction::Next) => move_log(&mut app, true),
+                            Some(KeyAction::Prev) => move_log(&mut app, false),
                             _ => {}
                         },
-                        Transition::Files => match event.code {
-                            KeyCode::Esc | KeyCode::Char('d') => app.transition = Transition::MainScreen,
-                            KeyCode::Left | KeyCode::Char('h') => {
-                                let selected = app.tree_state.selected();
+                        Transition::Settings => match app.keymap.action(Mode::Settings, &event) {
+                            // the title said what's unsaved, Esc drops it
+                            Some(KeyAction::Back) => {
+                                app.settings_changed = serde_json::Map::new();
+                                app.transition = Transition::MainScreen;
+                            }
+                            Some(KeyAction::Save) => {
+                                if let SettingsTarget::Torrents(ids) = &app.settings_target {
+                                    if !app.settings_changed.is_empty() {
+                                        let changed = std::mem::take(&mut app.settings_changed);
+                                        match serde_json::from_value::<TorrentSet>(serde_json::Value::Object(changed)) {
+                                            Ok(torrent_set) => {
+                                                let torrent_set = TorrentSet {
+                                                    ids: ids.clone(),
+                                                    ..torrent_set
+                                                };
+                                                send_torrent_set(&mut app, &sender, torrent_set);
+                                            }
+                                            Err(error) => {
+                                                app.events.push(
+                                                    EventKind::Error,
+                                                    "Can't save settings",
+                                                    error.to_string(),
+                                                );
+                                            }
+                                        }
+                                    }
+                                    app.transition = Transition::MainScreen;
+                                }
+                            }
+                            Some(KeyAction::Next) => move_setting(&mut app, true),
+                            Some(KeyAction::Prev) => move_setting(&mut app, false),
+                            Some(KeyAction::Edit) => {
+                                if let Some(setting) = selected_setting(&app) {
+                                    let value = settings_values(&app).and_then(|x| x.get(setting.key));
+                                    if let Some(value) = toggle_value(setting, value) {
+                                        save_setting(&mut app, setting, value, &sender);
+                                    } else if value.is_some() {
+                                        app.input = edit_value(setting, value);
+                                        app.input_error = None;
+                                        app.transition = Transition::EditSetting;
+                                    }
+                                }
+                            }
+                            _ => {}
+                        },
+                        Transition::EditSetting => match event.code {
+                            KeyCode::Esc => {
+                                app.input = "".to_string();
+                                app.input_error = None;
+                                app.transition = Transition::Settings;
+                            }
+                            KeyCode::Enter => {
+                                if let Some(setting) = selected_setting(&app) {
+                                    match parse_value(setting, &app.input) {
+                                        Ok(value) => {
+                                            save_setting(&mut app, setting, value, &sender);
+                                            app.input = "".to_string();
+                                            app.input_error = None;
+                                            app.transition = Transition::Settings;
+                                        }
+                                        Err(error) => app.input_error = Some(error),
+                                    }
+                                }
+                            }
+                            KeyCode::Backspace => {
+                                app.input.pop();
+                                app.input_error = None;
+                            }
+                            KeyCode::Char(c) => {
+                                app.input.push(c);
+                                app.input_error = None;
+                            }
+                            _ => {}
+                        },
+                        Transition::RenameFile => match event.code {
+                            KeyCode::Esc => {
+                                app.input = "".to_string();
+                                app.transition = Transition::Files;
+                            }
+                            KeyCode::Enter if !app.input.trim().is_empty() && !app.input.contains('/') => {
+                                if let Some((id, path)) = selected_path(&app) {
+                                    let name = app.input.trim().to_string();
+                                    send_cmd(&mut app, &sender, TorrentCmd::RenamePath(id, path, name));
+                                }
+                                app.input = "".to_string();
+                                app.transition = Transition::Files;
+                            }
+                            KeyCode::Backspace => {
+                                app.input.pop();
+                            }
+                            KeyCode::Char(c) => app.input.push(c),
+                            _ => {}
+                        },
+                        Transition::EditLabels => match event.code {
+                            KeyCode::Esc => {
+                                app.input = "".to_string();
+                                app.transition = Transition::MainScreen;
+                            }
+                            KeyCode::Enter => {
+                                if let Some(id) = app
+                                    .main_table_state
+                                    .selected()
+                                    .and_then(|x| app.filtered_torrents.get(x))
+                                    .map(|x| x.id)
+                                {
+                                    let labels: Vec<String> = app
+                                        .input
+                                        .split(',')
+                                        .map(|l| l.trim().to_string())
+                                        .filter(|l| !l.is_empty())
+                                        .collect();
+                                    send_torrent_set(&mut app, &sender, TorrentSet {
+                                        ids: vec![id],
+                                        labels: Some(labels),
+                                        ..TorrentSet::default()
+                                    });
+                                }
+                                app.input = "".to_string();
+                                app.transition = Transition::MainScreen;
+                            }
+                            KeyCode::Backspace => {
+                                app.input.pop();
+                            }
+                            KeyCode::Char(c) => app.input.push(c),
+                            _ => {}
+                        },
+                        Transition::Move => match event.code {
+                            KeyCode::Esc => {
+                                app.transition = Transition::MainScreen;
+                            }
+                            KeyCode::Char(c) => {
+                                let ids = target_ids(&app);
+                                if !ids.is_empty() {
+                                    if let Some((f, _, _)) = app.folder_mapping.iter().find(|y| y.1 == c) {
+                                        let cmd = TorrentCmd::Move(ids, f.to_string(), true);
+                                        send_cmd(&mut app, &sender, cmd);
+                                        app.transition = Transition::MainScreen;
+                                    }
+                                    /*if let Some(f) = app.groups.folder_keys.get(&c) {
+                                    }*/
+                                }
+                            }
+                            _ => {}
+                        },
+                        Transition::ConfirmRemove(with_data) => match event.code {
+                            KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => {
+                                app.transition = Transition::MainScreen;
+                            }
+                            KeyCode::Char('y') => {
+                                let ids = target_ids(&app);
+                                if !ids.is_empty() {
+                                    app.marked.retain(|x| !ids.contains(x));
+                                    send_cmd(&mut app, &sender, TorrentCmd::Delete(ids, with_data));
+                                }
+                                app.transition = Transition::MainScreen;
+                            }
+                            _ => {}
+                        },
+                        Transition::Files => match app.keymap.action(Mode::Files, &event) {
+                            Some(KeyAction::Back) => app.transition = Transition::MainScreen,
+                            Some(KeyAction::Collapse) => {
+                                let selected = app.tree_state.selected();
                                 if !app.tree_state.close(&selected) {
                                     let (head, _) = get_identifier_without_leaf(&selected);
                                     app.tree_state.select(head);
                                 }
                             }
-                            KeyCode::Right | KeyCode::Char('l') => {
+                            Some(KeyAction::Expand) => {
                                 app.tree_state.open(app.tree_state.selected());
                             }
-                            KeyCode::Enter => app.tree_state.toggle(),
-                            KeyCode::Down | KeyCode::Char('j') => move_up_down(&mut app, true),
-                            KeyCode::Up | KeyCode::Char('k') => move_up_down(&mut app, false),
-                            KeyCode::Char(' ') => {
+                            Some(KeyAction::Toggle) => app.tree_state.toggle(),
+                            Some(KeyAction::Next) => move_up_down(&mut app, true),
+                            Some(KeyAction::Prev) => move_up_down(&mut app, false),
+                            Some(KeyAction::FileActions) => {
                                 app.transition = Transition::FileAction;
                             }
+                            Some(KeyAction::Trackers) => open_tab(&mut app, DetailsTab::Trackers, &sender),
+                            Some(KeyAction::Peers) => open_tab(&mut app, DetailsTab::Peers, &sender),
+                            Some(KeyAction::SortColumn) => {
+                                app.file_sort = app.file_sort.next();
+                                reset_file_tree(&mut app);
+                            }
+                            Some(KeyAction::ReverseSort) => {
+                                app.file_sort_reverse = !app.file_sort_reverse;
+                                reset_file_tree(&mut app);
+                            }
+                            Some(KeyAction::Filter) => {
+                                app.input = app.file_filter.clone();
+                                app.transition = Transition::FileFilter;
+                            }
                             _ => {}
                         },
-                        Transition::ChooseSortFunc => match event.code {
+                        // narrows the tree with every key, Esc drops the filter
+                        Transition::FileFilter => match event.code {
                             KeyCode::Esc => {
-                                app.transition = Transition::MainScreen;
+                                app.input.clear();
+                                app.file_filter.clear();
+                                reset_file_tree(&mut app);
+                                app.transition = Transition::Files;
                             }
-                            KeyCode::Char('d') => {
-                                app.sort_func = SortFunction {
-                                    name: String::from("Date Added"),
-                                    func: by_date_added,
-                                };
-                                (app.sort_func.func)(&mut app.filtered_torrents);
-                                app.transition = Transition::MainScreen;
+                            KeyCode::Enter => app.transition = Transition::Files,
+                            KeyCode::Backspace => {
+                                app.input.pop();
+                                app.file_filter = app.input.to_lowercase();
+                                reset_file_tree(&mut app);
                             }
-                            KeyCode::Char('s') => {
-                                app.sort_func = SortFunction {
-                                    name: String::from("by size"),
-                                    func: by_size,
-                                };
-                                (app.sort_func.func)(&mut app.filtered_torrents);
+                            KeyCode::Char(c) => {
+                                app.input.push(c);
+                                app.file_filter = app.input.to_lowercase();
+                                reset_file_tree(&mut app);
+                            }
+                            _ => {}
+                        },
+                        Transition::General | Transition::Pieces => match app.keymap.action(Mode::Details, &event) {
+                            Some(KeyAction::Back) => app.transition = Transition::MainScreen,
+                            Some(KeyAction::Files) => open_tab(&mut app, DetailsTab::Files, &sender),
+                            Some(KeyAction::Trackers) => open_tab(&mut app, DetailsTab::Trackers, &sender),
+                            Some(KeyAction::Peers) => open_tab(&mut app, DetailsTab::Peers, &sender),
+                            _ => {}
+                        },
+                        Transition::Peers => match app.keymap.action(Mode::Peers, &event) {
+                            Some(KeyAction::Back) => app.transition = Transition::MainScreen,
+                            Some(KeyAction::Files) => open_tab(&mut app, DetailsTab::Files, &sender),
+                            Some(KeyAction::Trackers) => open_tab(&mut app, DetailsTab::Trackers, &sender),
+                            Some(KeyAction::Next) => move_peer(&mut app, true),
+                            Some(KeyAction::Prev) => move_peer(&mut app, false),
+                            Some(KeyAction::SortColumn) => app.peers_sort = app.peers_sort.next(),
+                            Some(KeyAction::ReverseSort) => app.peers_sort_asc = !app.peers_sort_asc,
+                            _ => {}
+                        },
+                        Transition::Trackers => match app.keymap.action(Mode::Trackers, &event) {
+                            Some(KeyAction::Back) => app.transition = Transition::MainScreen,
+                            Some(KeyAction::Files) => open_tab(&mut app, DetailsTab::Files, &sender),
+                            Some(KeyAction::Peers) => open_tab(&mut app, DetailsTab::Peers, &sender),
+                            Some(KeyAction::Next) => move_tracker(&mut app, true),
+                            Some(KeyAction::Prev) => move_tracker(&mut app, false),
+                            Some(KeyAction::AddTracker) => {
+                                app.input = "".to_string();
+                                app.transition = Transition::TrackerInput(TrackerEdit::Add);
+                            }
+                            Some(KeyAction::EditTracker) => {
+                                if let Some((_, tracker)) = selected_tracker(&app) {
+                                    let edit = TrackerEdit::Edit(tracker.id);
+                                    app.input = tracker.announce.clone();
+                                    app.transition = Transition::TrackerInput(edit);
+                                }
+                            }
+                            Some(KeyAction::RemoveTracker) => {
+                                if let Some((id, tracker)) = selected_tracker(&app) {
+                                    let cmd = TorrentCmd::Trackers(id, TrackerCmd::Remove(tracker.id));
+                                    send_cmd(&mut app, &sender, cmd);
+                                }
+                            }
+                            Some(KeyAction::ReplaceTrackers) => {
+                                app.input = selected_tracker(&app)
+                                    .map(|(_, t)| tracker_origin(&t.announce))
+                                    .unwrap_or_default();
+                                app.transition = Transition::TrackerInput(TrackerEdit::ReplaceFrom);
+                            }
+                            _ => {}
+                        },
+                        Transition::TrackerInput(ref edit) => {
+                            let edit = edit.clone();
+                            match event.code {
+                                KeyCode::Esc => {
+                                    app.input = "".to_string();
+                                    app.transition = Transition::Trackers;
+                                }
+                                KeyCode::Enter if !app.input.trim().is_empty() => {
+                                    let input = app.input.trim().to_string();
+                                    let torrent_id = app.details.as_ref().map(|d| d.id as i64);
+                                    let cmd = match (edit, torrent_id) {
+                                        (TrackerEdit::ReplaceFrom, _) => {
+                                            app.transition = Transition::TrackerInput(TrackerEdit::ReplaceTo(input));
+                                            None
+                                        }
+                                        (TrackerEdit::ReplaceTo(from), _) => {
+                                            Some(TorrentCmd::ReplaceTrackerPrefix(from, input))
+                                        }
+                                        (TrackerEdit::Add, Some(id)) => Some(TorrentCmd::Trackers(id, TrackerCmd::Add(input))),
+                                        (TrackerEdit::Edit(tracker_id), Some(id)) => {
+                                            Some(TorrentCmd::Trackers(id, TrackerCmd::Replace(tracker_id, input)))
+                                        }
+                                        (_, None) => None,
+                                    };
+                                    if let Some(cmd) = cmd {
+                                        send_cmd(&mut app, &sender, cmd);
+                                        app.input = "".to_string();
+                                        app.transition = Transition::Trackers;
+                                    }
+                                }
+                                KeyCode::Backspace => {
+                                    app.input.pop();
+                                }
+                                KeyCode::Char(c) => app.input.push(c),
+                                _ => {}
+                            }
+                        }
+                        Transition::ChooseSortFunc => match app.keymap.action(Mode::Sort, &event) {
+                            Some(KeyAction::Back) => {
+                                app.sort_then = false;
                                 app.transition = Transition::MainScreen;
                             }
-                            KeyCode::Char('r') => {
-                                app.sort_func = SortFunction {
-                                    name: String::from("by ratio"),
-                                    func: by_ratio,
-                                };
-                                (app.sort_func.func)(&mut app.filtered_torrents);
+                            Some(KeyAction::SecondaryKey) => app.sort_then = !app.sort_then,
+                            Some(KeyAction::Invert) => {
+                                let mut sort_func = app.sort_func();
+                                sort_func.descending = !sort_func.descending;
+                                app.set_sort_func(sort_func);
                                 app.transition = Transition::MainScreen;
                             }
-                            KeyCode::Char('u') => {
-                                app.sort_func = SortFunction {
-                                    name: String::from("by uploaded"),
-                                    func: by_uploaded,
+                            Some(KeyAction::SortBy(key)) => {
+                                let current = app.sort_func();
+                                let sort_func = if app.sort_then {
+                                    // picking the primary key again drops the secondary one
+                                    SortFunction {
+                                        then: Some(key).filter(|x| *x != current.key),
+                                        ..current
+                                    }
+                                } else {
+                                    SortFunction {
+                                        then: current.then.filter(|x| *x != key),
+                                        ..SortFunction::new(key)
+                                    }
                                 };
-                                (app.sort_func.func)(&mut app.filtered_torrents);
+                                app.set_sort_func(sort_func);
+                                app.sort_then = false;
                                 app.transition = Transition::MainScreen;
                             }
                             _ => {}
@@ -805,34 +1414,76 @@ fn run_app<B: Backend>(
                             }
                             _ => {}
                         },
-                        Transition::FileAction => match event.code {
-                            KeyCode::Esc => app.transition = Transition::Files,
-                            KeyCode::Char(c) => {
-                                if let Some(details) = &app.details {
-                                    if let Some(file_idx) =
-                                        find_file_position(&app.tree_state.selected(), &app.tree_index) {
-                                        if let Some(_file) = app.tree_state.selected().first().and_then(|x| details.files.get(*x)) {
-                                            if let Some(idx) = app
-                                                .config
-                                                .file_actions
-                                                .iter()
-                                                .enumerate()
-                                                .find(|x| x.1.shortcut.starts_with(c)) {
-                                                sender
-                                                    .blocking_send(TorrentCmd::FileAction(details.id, idx.0, file_idx))
-                                                    .expect("should send");
-                                                app.transition = Transition::Files;
-                                            }
-                                        }
-                                    }
+                        Transition::FileAction => match app.keymap.action(Mode::FileAction, &event) {
+                            Some(KeyAction::Back) => app.transition = Transition::Files,
+                            Some(KeyAction::Download) => {
+                                if let Some((id, files)) = selected_files(&app) {
+                                    send_torrent_set(&mut app, &sender, TorrentSet {
+                                        ids: vec![id],
+                                        files_wanted: Some(files),
+                                        ..TorrentSet::default()
+                                    });
+                                }
+                                app.transition = Transition::Files;
+                            }
+                            Some(KeyAction::Skip) => {
+                                if let Some((id, files)) = selected_files(&app) {
+                                    send_torrent_set(&mut app, &sender, TorrentSet {
+                                        ids: vec![id],
+                                        files_unwanted: Some(files),
+                                        ..TorrentSet::default()
+                                    });
+                                }
+                                app.transition = Transition::Files;
+                            }
+                            Some(KeyAction::LowPriority) => {
+                                if let Some((id, files)) = selected_files(&app) {
+                                    send_torrent_set(&mut app, &sender, TorrentSet {
+                                        ids: vec![id],
+                                        priority_low: Some(files),
+                                        ..TorrentSet::default()
+                                    });
+                                }
+                                app.transition = Transition::Files;
+                            }
+                            Some(KeyAction::NormalPriority) => {
+                                if let Some((id, files)) = selected_files(&app) {
+                                    send_torrent_set(&mut app, &sender, TorrentSet {
+                                        ids: vec![id],
+                                        priority_normal: Some(files),
+                                        ..TorrentSet::default()
+                                    });
+                                }
+                                app.transition = Transition::Files;
+                            }
+                            Some(KeyAction::HighPriority) => {
+                                if let Some((id, files)) = selected_files(&app) {
+                                    send_torrent_set(&mut app, &sender, TorrentSet {
+                                        ids: vec![id],
+                                        priority_high: Some(files),
+                                        ..TorrentSet::default()
+                                    });
+                                }
+                                app.transition = Transition::Files;
+                            }
+                            Some(KeyAction::Rename) => {
+                                if let Some((_, path)) = selected_path(&app) {
+                                    app.input = path.rsplit('/').next().unwrap_or_default().to_string();
+                                    app.transition = Transition::RenameFile;
+                                }
+                            }
+                            None => if let KeyCode::Char(c) = event.code {
+                                let cmd = app.details.as_ref().and_then(|details| {
+                                    let file_idx = find_file_position(&app.tree_state.selected(), &app.tree_index)?;
+                                    app.tree_state.selected().first().and_then(|x| details.files.get(*x))?;
+                                    let idx = app.config.file_actions.iter().position(|x| x.shortcut.starts_with(c))?;
+                                    Some(TorrentCmd::FileAction(details.id, idx, file_idx))
+                                });
+                                if let Some(cmd) = cmd {
+                                    send_cmd(&mut app, &sender, cmd);
+                                    app.transition = Transition::Files;
                                 }
                             }
-                            /*KeyCode::Char('+') => {}
-                            KeyCode::Char('-') => {}
-                            KeyCode::Char('r') => {}
-                            KeyCode::Char('l') => { /*torrent id, file idx, l/m/h, files-wanted, files-unwanted*/ }
-                            KeyCode::Char('m') => {}
-                            KeyCode::Char('h') => {}*/
                             _ => {}
                         },
                     }
@@ -840,8 +1491,23 @@ fn run_app<B: Backend>(
             },
 
             Some(TorrentUpdate::Partial(json, removed, _i, session_stats, free_space_opt, details)) => {
+                if app.connection_lost {
+                    app.connection_lost = false;
+                    let connection = &app.config.connections[app.connection_idx];
+                    app.events.push(
+                        EventKind::Connected,
+                        format!("Connection to {} restored", connection.name),
+                        connection.url.clone(),
+                    );
+                }
                 app.details = *details;
-                app.err = None;
+                if matches!(
+                    app.transition,
+                    Transition::Files | Transition::FileAction | Transition::RenameFile | Transition::FileFilter
+                ) {
+                    // tree structure stays the same, only wanted/priority marks change, and the order by completion
+                    rebuild_file_tree(&mut app);
+                }
 
                 if let Some(s) = *session_stats {
                     if app.upload_data.len() > 200 {
@@ -865,86 +1531,80 @@ fn run_app<B: Backend>(
                     .map(|x| x.as_i64().unwrap())
                     .collect();
                 for k in removed {
-                    app.torrents.remove(&k);
+                    if let Some(x) = app.torrents.remove(&k) {
+                        app.events.push(EventKind::Removed, "Torrent removed", x.name);
+                    }
+                    app.marked.remove(&k);
                 }
                 let xs = json.as_array().unwrap().clone();
 
                 //let prev_length = app.torrents.len();
                 //let prev_filtered_length = app.filtered_torrents.len();
 
+                let header = xs.first().and_then(|x| x.as_array()).cloned().unwrap_or_default();
+                // before the full update arrives every torrent looks new
+                let loaded = !app.torrents.is_empty();
                 for x in xs.iter().skip(1) {
                     let ys = x.as_array().unwrap();
                     let id = ys[0].as_i64().unwrap();
                     if let Some(y) = app.torrents.get_mut(&id) {
-                        y.update(ys);
+                        // a verified torrent may reach 100% too, that's no news
+                        let was_done = y.percent_done >= 1.0
+                            || matches!(y.status, TorrentStatus::Verifying | TorrentStatus::VerifyQueued);
+                        let had_error = y.error != 0;
+                        y.update(&header, ys);
+                        if !was_done && y.percent_done >= 1.0 {
+                            app.events.push(EventKind::Completed, "Torrent completed", y.name.clone());
+                        }
+                        if !had_error && y.error != 0 {
+                            app.events.push(EventKind::TorrentError, y.name.clone(), y.error_string.clone());
+                        }
                     } else {
                         let info =
-                            TorrentInfo::from_json(x).map_err(|r| std::io::Error::new(std::io::ErrorKind::Other, r))?;
+                            TorrentInfo::from_json(&header, x).map_err(|r| std::io::Error::new(std::io::ErrorKind::Other, r))?;
+                        if loaded {
+                            app.events.push(EventKind::Added, "Torrent added", info.name.clone());
+                        }
                         app.torrents.insert(id, info);
                     }
                 }
                 app.num_active = xs.len() - 1;
+                app.active_ids = xs.iter().skip(1).filter_map(|x| x[0].as_i64()).collect();
                 //if app.torrents.len() != prev_length || app.filtered_torrents.is_empty() {
-                app.groups = update_torrent_stats(&app.torrents);
+                app.groups = update_torrent_stats(
+                    &app.torrents,
+                    &app.views,
+                    app.config.finished_days as i64 * 86400,
+                    &app.config.connections[app.connection_idx].download_dir,
+                );
                 //}
-                match app.current_filter.clone() {
-                    Filter::Search(text) => {
-                        app.filtered_torrents = app
-                            .torrents
-                            .values()
-                            .filter(|x| x.name.to_lowercase().contains(&text.to_lowercase()))
-                            .cloned()
-                            .collect();
-                        (app.sort_func.func)(&mut app.filtered_torrents);
-                    }
-                    Filter::ByDirectory(_) => {
-                        if let Filter::ByDirectory(d) = app.current_filter.clone() {
-                            app.filtered_torrents =
-                                app.torrents.values().filter(|x| x.download_dir == d).cloned().collect();
-                            (app.sort_func.func)(&mut app.filtered_torrents);
-                        }
-                    }
-                    Filter::ByStatus(_) => {
-                        if let Filter::ByStatus(s) = app.current_filter.clone() {
-                            app.filtered_torrents = app.torrents.values().filter(|x| x.status == s).cloned().collect();
-                            (app.sort_func.func)(&mut app.filtered_torrents);
-                        }
-                    }
-                    Filter::All => {
-                        app.filtered_torrents = app.torrents.values().cloned().collect();
-                        (app.sort_func.func)(&mut app.filtered_torrents);
-                    }
-                    Filter::Active => {
-                        app.filtered_torrents = xs.iter().skip(1).map(TorrentInfo::new).collect();
-                        (app.sort_func.func)(&mut app.filtered_torrents);
-                    }
-                    Filter::Recent => {
-                        // if app.torrents.len() != prev_length || app.filtered_torrents.is_empty() {
-                        app.filtered_torrents = most_recent_items(&app.torrents);
-                        //}
-                        if app.sort_func.name != "Date Added" {
-                            (app.sort_func.func)(&mut app.filtered_torrents);
-                        }
-                    }
-                    Filter::Error => {
-                        app.filtered_torrents = app.torrents.values().filter(|x| x.error > 0).cloned().collect();
-                        (app.sort_func.func)(&mut app.filtered_torrents);
-                    }
-                }
+                apply_filters(&mut app);
                 if app.main_table_state.selected().is_none() {
                     select_first_torrent(&mut app, sender.clone());
                 }
             }
             Some(TorrentUpdate::Full(xs)) => {
+                let xs = xs.as_array().unwrap();
+                let header = xs.first().and_then(|x| x.as_array()).cloned().unwrap_or_default();
                 let ts = xs
-                    .as_array()
-                    .unwrap()
                     .iter()
                     .skip(1)
-                    .map(TorrentInfo::new)
+                    .map(|x| TorrentInfo::new(&header, x))
                     .map(|it| (it.id, it));
                 app.torrents = HashMap::from_iter(ts);
-                app.groups = update_torrent_stats(&app.torrents);
+                app.connection_lost = false;
+                let connection = &app.config.connections[app.connection_idx];
+                app.events.push(
+                    EventKind::Connected,
+                    format!("Connected to {}", connection.name),
+                    format!("{} torrents at {}", app.torrents.len(), connection.url),
+                );
+                app.groups = update_torrent_stats(
+                    &app.torrents,
+                    &app.views,
+                    app.config.finished_days as i64 * 86400,
+                    &app.config.connections[app.connection_idx].download_dir,
+                );
                 app.left_filter_state.select(Some(0));
                 let _ = sender.blocking_send(TorrentCmd::Tick(0));
 
@@ -954,23 +1614,391 @@ fn run_app<B: Backend>(
                 //app.filtered_torrents = xs;
             }
             Some(TorrentUpdate::Details(details)) => {
+                // same torrent (e.g. after a rename) keeps the cursor and opened folders
+                let same_torrent = app.details.as_ref().map(|d| d.id) == Some(details.id);
                 app.details = Some(*details);
-                if let Some(d) = &app.details {
-                    app.tree_items = build_file_tree(&d.files, app.config.show_icons);
-                    app.tree_index = build_file_tree_index(&d.files);
+                if !same_torrent {
                     app.tree_state = TreeState::default();
+                    app.file_filter.clear();
+                }
+                rebuild_file_tree(&mut app);
+                // the files arrive after switching to the tab
+                if app.transition == Transition::Files && app.tree_state.selected().is_empty() {
+                    open_first_level(&mut app);
+                    move_up_down(&mut app, true);
+                }
+            }
+            Some(TorrentUpdate::TorrentAdded { name, duplicate: true }) => {
+                app.events.push(EventKind::Info, "Torrent already exists", name);
+            }
+            // new ones get logged when they show up in the next update
+            Some(TorrentUpdate::TorrentAdded { .. }) => {}
+            Some(TorrentUpdate::TorrentSettings(settings)) => {
+                if app.settings_changed.is_empty() {
+                    app.torrent_settings = settings;
+                }
+            }
+            Some(TorrentUpdate::Done { cmd_id, op, ids }) => {
+                app.pending.remove(&cmd_id);
+                // added torrents are logged when they show up
+                if op != Operation::Add {
+                    let details = describe_torrents(&app, &ids);
+                    app.events.push(EventKind::Info, format!("{} done", op.name()), details);
                 }
             }
+            Some(TorrentUpdate::Failed { cmd_id, op, ids, error }) => {
+                app.pending.remove(&cmd_id);
+                let details = if ids.is_empty() {
+                    error
+                } else {
+                    format!("{}\n{}", describe_torrents(&app, &ids), error)
+                };
+                app.events.push(EventKind::Error, format!("{} failed", op.name()), details);
+            }
             Some(TorrentUpdate::Session(session)) => {
                 if app.config.connections[app.connection_idx].download_dir.is_empty() {
-                    app.config.connections[app.connection_idx].download_dir = session.download_dir;
+                    app.config.connections[app.connection_idx].download_dir = session.download_dir.clone();
                 }
+                app.session = Some(session);
             }
             None => {}
         }
     }
 }
 
+// marked torrents together with the pending visual range
+pub fn marked_ids(app: &App) -> HashSet<i64> {
+    let mut marked = app.marked.clone();
+    let start = app
+        .visual_start
+        .and_then(|id| app.filtered_torrents.iter().position(|x| x.id == id));
+    if let (Some(start), Some(end)) = (start, app.main_table_state.selected()) {
+        let (from, to) = (start.min(end), start.max(end));
+        marked.extend(app.filtered_torrents.iter().skip(from).take(to + 1 - from).map(|x| x.id));
+    }
+    marked
+}
+
+fn commit_visual_range(app: &mut App) {
+    app.marked = marked_ids(app);
+    app.visual_start = None;
+}
+
+// batch actions apply to the marked torrents, or to the selected one when nothing is marked
+pub fn target_ids(app: &App) -> Vec<i64> {
+    let marked = marked_ids(app);
+    if marked.is_empty() {
+        app.selected.iter().map(|x| x.id).collect()
+    } else {
+        let mut ids: Vec<i64> = marked.into_iter().collect();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+fn handle_mouse(app: &mut App, event: MouseEvent, sender: &Sender<TorrentCmd>) -> Option<KeyEvent> {
+    let hit = |area: Option<Rect>| {
+        area.filter(|a| {
+            event.column >= a.x && event.column < a.x + a.width && event.row >= a.y && event.row < a.y + a.height
+        })
+    };
+    if event.kind == MouseEventKind::Down(MouseButton::Left) {
+        if let Some(x) = app.areas.menu.iter().find(|x| hit(Some(x.0)).is_some()) {
+            return Some(x.1);
+        }
+    }
+    match (&app.transition, event.kind) {
+        // nothing to scroll when the filters left no torrents
+        (Transition::MainScreen, MouseEventKind::ScrollDown | MouseEventKind::ScrollUp)
+            if app.filtered_torrents.is_empty() => {}
+        (Transition::MainScreen, MouseEventKind::ScrollDown) => select_next_torrent(app, sender),
+        (Transition::MainScreen, MouseEventKind::ScrollUp) => select_prev_torrent(app, sender),
+        (Transition::Files, MouseEventKind::ScrollDown) => move_up_down(app, true),
+        (Transition::Files, MouseEventKind::ScrollUp) => move_up_down(app, false),
+        (Transition::MainScreen | Transition::Filter, MouseEventKind::Down(MouseButton::Left)) => {
+            let chip = app.areas.chips.iter().find(|x| hit(Some(x.1)).is_some()).map(|x| x.0);
+            if let Some(facet) = chip {
+                remove_facet(app, facet);
+                apply_filters(app);
+                select_first_torrent(app, sender.clone());
+            } else if let Some(area) = hit(app.areas.filters) {
+                // the area has borders, rows start on the second line
+                let row = (event.row - area.y) as usize;
+                let idx = row
+                    .checked_sub(1)
+                    .filter(|i| i + 2 < area.height as usize)
+                    .map(|i| app.areas.filters_offset + i);
+                let folder = idx
+                    .and_then(|x| x.checked_sub(FOLDERS_START))
+                    .and_then(|i| app.folder_rows().get(i).cloned());
+                if let Some(row) = folder {
+                    // the arrow opens and closes, the rest of the row filters
+                    let arrow = area.x + 2 + 2 * row.depth as u16;
+                    if row.has_children && (event.column == arrow || event.column == arrow + 1) {
+                        set_folder_expanded(app, &row.path, !row.expanded);
+                    } else {
+                        select_folder(app, row.path, sender);
+                    }
+                } else if let Some(idx) = idx {
+                    if let Some(filter) = filter_at(app, idx) {
+                        apply_filter(app, filter, idx, sender);
+                    }
+                }
+            } else if let Some(area) = hit(app.areas.table) {
+                if event.row == area.y {
+                    let column = app
+                        .areas
+                        .table_columns
+                        .iter()
+                        .find(|(_, x, width)| event.column >= *x && event.column < x + width)
+                        .map(|x| x.0);
+                    if let Some(key) = column.and_then(sort_by_column) {
+                        // clicking the sorted column again flips the direction
+                        let current = app.sort_func();
+                        let sort_func = if current.key == key {
+                            SortFunction {
+                                descending: !current.descending,
+                                ..current
+                            }
+                        } else {
+                            SortFunction::new(key)
+                        };
+                        app.set_sort_func(sort_func);
+                    }
+                } else {
+                    let idx = app.areas.table_offset + (event.row - area.y) as usize - 1;
+                    if let Some(x) = app.filtered_torrents.get(idx) {
+                        app.selected = Some(x.clone());
+                        sender.blocking_send(TorrentCmd::GetDetails(x.id)).expect("should send");
+                        app.main_table_state.select(Some(idx));
+                    }
+                }
+                app.transition = Transition::MainScreen;
+            }
+        }
+        (Transition::Files, MouseEventKind::Down(MouseButton::Left)) => {
+            if let Some(area) = hit(app.areas.tree) {
+                let row = (event.row - area.y) as usize;
+                let visible = flatten(&app.tree_state.get_all_opened(), &app.tree_items);
+                if let Some(x) = row.checked_sub(1).and_then(|i| visible.get(app.areas.tree_offset + i)) {
+                    if x.identifier == app.tree_state.selected() {
+                        app.tree_state.toggle();
+                    } else {
+                        app.tree_state.select(x.identifier.clone());
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+    None
+}
+
+// the filter list: status filters, a separator, the folder tree, then labels and views after more separators
+pub fn filter_at(app: &App, idx: usize) -> Option<Filter> {
+    if let Some(filter) = builtin_filter(idx) {
+        return Some(filter);
+    }
+    let rows = app.folder_rows();
+    let folders = rows.len();
+    if idx >= FOLDERS_START && idx < FOLDERS_START + folders {
+        Some(Filter::ByDirectory(rows[idx - FOLDERS_START].path.clone()))
+    } else if idx >= views_start(app) {
+        let i = idx - views_start(app);
+        if i < app.config.views.len() {
+            Some(Filter::View(i))
+        } else {
+            None
+        }
+    } else if idx > FOLDERS_START + folders {
+        let mut labels: Vec<&String> = app.groups.labels.keys().collect();
+        labels.sort();
+        labels.get(idx - FOLDERS_START - 1 - folders).map(|x| Filter::ByLabel(x.to_string()))
+    } else {
+        None
+    }
+}
+
+// first row of the saved views, they come after the folders and labels with a separator
+pub fn views_start(app: &App) -> usize {
+    let labels = if app.groups.labels.is_empty() { 0 } else { app.groups.labels.len() + 1 };
+    FOLDERS_START + 1 + app.folder_rows().len() + labels
+}
+
+// folder, view and label keys from calculate_*_keys, the built-in filters go through the keymap.
+// Picking an active filter again removes it
+fn apply_filter_key(app: &mut App, c: char, sender: &Sender<TorrentCmd>) {
+    let (filter, idx) = if let Some(x) = app.folder_mapping.iter().find(|x| x.1 == c) {
+        let folder = x.0.clone();
+        select_folder(app, folder, sender);
+        return;
+    } else if let Some(x) = app.label_mapping.iter().find(|x| x.1 == c) {
+        let label = x.0.clone();
+        let mut labels: Vec<&String> = app.groups.labels.keys().collect();
+        labels.sort();
+        let idx = FOLDERS_START + 1 + app.folder_rows().len() + labels.iter().position(|y| **y == label).unwrap_or(0);
+        (Filter::ByLabel(label), idx)
+    } else if let Some(x) = app.view_mapping.iter().find(|x| x.1 == c) {
+        let i = app.config.views.iter().position(|y| y.name == x.0).unwrap_or(0);
+        (Filter::View(i), views_start(app) + i)
+    } else {
+        return;
+    };
+    apply_filter(app, filter, idx, sender);
+}
+
+// a folder filters to everything beneath it and opens to show its subfolders
+fn select_folder(app: &mut App, path: String, sender: &Sender<TorrentCmd>) {
+    app.expanded_folders.insert(path.clone());
+    let idx = FOLDERS_START + app.folder_rows().iter().position(|x| x.path == path).unwrap_or(0);
+    apply_filter(app, Filter::ByDirectory(path), idx, sender);
+}
+
+fn apply_filter(app: &mut App, filter: Filter, idx: usize, sender: &Sender<TorrentCmd>) {
+    toggle_filter(app, filter);
+    app.left_filter_state.select(Some(idx));
+    app.transition = Transition::MainScreen;
+    apply_filters(app);
+    select_first_torrent(app, sender.clone());
+}
+
+// replaces the filter of the same facet, All just clears the status
+fn toggle_filter(app: &mut App, filter: Filter) {
+    let active = app.filters.contains(&filter);
+    remove_facet(app, filter.facet());
+    if !active && filter != Filter::All {
+        app.filters.push(filter);
+        app.filters.sort_by_key(|x| x.facet());
+    }
+}
+
+// All is the status filter in effect when there is none
+pub fn is_filter_active(app: &App, filter: &Filter) -> bool {
+    match filter {
+        Filter::All => !app.filters.iter().any(|x| x.facet() == Facet::Status),
+        _ => app.filters.contains(filter),
+    }
+}
+
+pub fn remove_facet(app: &mut App, facet: Facet) {
+    app.filters.retain(|x| x.facet() != facet);
+}
+
+fn apply_filters(app: &mut App) {
+    let now = unix_now();
+    let finished_within = app.config.finished_days as i64 * 86400;
+    let query = app
+        .filters
+        .iter()
+        .find_map(|x| match x {
+            Filter::Search(text) => Query::parse(text).ok(),
+            _ => None,
+        })
+        .unwrap_or_default();
+    let matches = |x: &TorrentInfo| {
+        app.filters.iter().all(|f| match f {
+            Filter::ByStatus(status) => x.status == *status,
+            Filter::ByDirectory(dir) => is_in_folder(&x.download_dir, dir),
+            Filter::ByLabel(label) => x.labels.contains(label),
+            Filter::Recent | Filter::All => true,
+            Filter::Active => app.active_ids.contains(&x.id),
+            Filter::Search(_) => query.matches(x, now),
+            Filter::Error => x.error > 0,
+            Filter::Stalled => x.is_stalled,
+            Filter::Finished => is_recently_finished(x, now, finished_within),
+            Filter::Incomplete => x.percent_done < 1.0,
+            Filter::Private => x.is_private,
+            Filter::AwaitingMetadata => x.metadata_percent_complete < 1.0,
+            Filter::View(i) => app.views[*i].matches(x, now),
+        })
+    };
+    let xs: Vec<TorrentInfo> = app.torrents.values().filter(|x| matches(x)).cloned().collect();
+    app.filtered_torrents = if app.filters.contains(&Filter::Recent) {
+        most_recent_items(xs, app.config.recent_count, app.config.recent_days, now)
+    } else {
+        xs
+    };
+    app.sort_func().sort(&mut app.filtered_torrents);
+
+    // the sidebar cursor doesn't stay on a filter that was just removed
+    let selected = app.left_filter_state.selected().and_then(|i| filter_at(app, i));
+    if selected.filter(|x| is_filter_active(app, x)).is_none() {
+        app.left_filter_state.select(None);
+    }
+}
+
+// `/` and `?` jump to the next match of the query, starting from where the search began
+fn find_torrent(app: &mut App, forward: bool, current: usize) {
+    let query = match Query::parse(&app.input) {
+        Ok(query) => query,
+        Err(error) => {
+            app.input_error = Some(error);
+            return;
+        }
+    };
+    app.input_error = None;
+    let now = unix_now();
+    let maybe_x = if forward {
+        app.filtered_torrents
+            .iter()
+            .enumerate()
+            .skip(current.saturating_sub(1))
+            .find(|x| query.matches(x.1, now))
+    } else {
+        app.filtered_torrents
+            .iter()
+            .enumerate()
+            .rev()
+            .skip(app.filtered_torrents.len().saturating_sub(current.saturating_add(1)))
+            .find(|x| query.matches(x.1, now))
+    };
+    if let Some((i, _)) = maybe_x {
+        app.main_table_state.select(Some(i));
+    }
+}
+
+fn select_next_torrent(app: &mut App, sender: &Sender<TorrentCmd>) {
+    if app.filtered_torrents.is_empty() {
+        return;
+    }
+    if let Some(selected) = app.main_table_state.selected() {
+        let amount_pets = app.filtered_torrents.len();
+        if selected >= amount_pets - 1 {
+            select_first_torrent(app, sender.clone());
+        } else {
+            app.main_table_state.select(Some(selected + 1));
+            app.selected = Some(app.filtered_torrents[selected + 1].clone());
+            sender.blocking_send(TorrentCmd::GetDetails(app.filtered_torrents[selected + 1].id)).expect("foo");
+        }
+    } else {
+        select_first_torrent(app, sender.clone());
+    }
+}
+
+fn select_prev_torrent(app: &mut App, sender: &Sender<TorrentCmd>) {
+    if !app.filtered_torrents.is_empty() {
+        if let Some(selected) = app.main_table_state.selected() {
+            let selected = selected.min(app.filtered_torrents.len());
+            let amount_pets = app.filtered_torrents.len();
+            if selected > 0 {
+                app.main_table_state.select(Some(selected - 1));
+                app.selected = Some(app.filtered_torrents[selected - 1].clone());
+                sender.blocking_send(TorrentCmd::GetDetails(app.filtered_torrents[selected - 1].id)).expect("foo");
+            } else {
+                app.main_table_state.select(Some(amount_pets - 1));
+                app.selected = Some(app.filtered_torrents[amount_pets - 1].clone());
+                sender.blocking_send(TorrentCmd::GetDetails(app.filtered_torrents[amount_pets - 1].id)).expect("foo");
+            }
+        } else {
+            select_first_torrent(app, sender.clone());
+        }
+    } else {
+        app.main_table_state.select(None);
+        app.selected = None;
+        let _ = sender.blocking_send(TorrentCmd::Select(None));
+    }
+}
+
 fn select_first_torrent(app: &mut App, sender: Sender<TorrentCmd>) {
     if !app.filtered_torrents.is_empty() {
         app.main_table_state.select(Some(0));
@@ -979,11 +2007,271 @@ fn select_first_torrent(app: &mut App, sender: Sender<TorrentCmd>) {
             .blocking_send(TorrentCmd::GetDetails(app.filtered_torrents[0].id))
             .expect("foo");
     } else {
+        // the filters left nothing, no row to come back to
+        app.main_table_state.select(None);
         app.selected = None;
         let _ = sender.blocking_send(TorrentCmd::Select(None));
     }
 }
 
+fn add_torrent(app: &mut App, download_dir: Option<String>, sender: &Sender<TorrentCmd>) {
+    let source = app.input.trim().to_string();
+    let cmd = if is_torrent_link(&source) {
+        Some(TorrentCmd::AddTorrent(download_dir, Some(source), None, app.add_paused))
+    } else {
+        match read_torrent_file(&source) {
+            Ok(metainfo) => Some(TorrentCmd::AddTorrent(download_dir, None, Some(metainfo), app.add_paused)),
+            Err(error) => {
+                app.events.push(EventKind::Error, "Can't read torrent file", format!("{}:\n\n{}", source, error));
+                None
+            }
+        }
+    };
+    if let Some(cmd) = cmd {
+        send_cmd(app, sender, cmd);
+    }
+    app.input = "".to_string();
+    app.transition = Transition::MainScreen;
+}
+
+// torrent id and all the files under the selected tree node
+fn selected_files(app: &App) -> Option<(i64, Vec<i64>)> {
+    app.details.as_ref().and_then(|details| {
+        let files = find_file_indices(&app.tree_state.selected(), &app.tree_index);
+        if files.is_empty() {
+            None
+        } else {
+            Some((details.id as i64, files.iter().map(|x| *x as i64).collect()))
+        }
+    })
+}
+
+fn selected_path(app: &App) -> Option<(i64, String)> {
+    app.details.as_ref().and_then(|details| {
+        find_node_path(&app.tree_state.selected(), &app.tree_index, &details.files).map(|x| (details.id as i64, x))
+    })
+}
+
+fn selected_tracker<'a>(app: &'a App) -> Option<(i64, &'a Tracker)> {
+    app.details.as_ref().and_then(|d| {
+        app.trackers_state
+            .selected()
+            .and_then(|i| d.trackers.get(i))
+            .map(|t| (d.id as i64, t))
+    })
+}
+
+fn move_tracker(app: &mut App, down: bool) {
+    let len = app.details.as_ref().map_or(0, |d| d.trackers.len());
+    if len > 0 {
+        let i = app.trackers_state.selected().unwrap_or(0).min(len - 1);
+        app.trackers_state
+            .select(Some(if down { (i + 1) % len } else { (i + len - 1) % len }));
+    }
+}
+
+fn move_peer(app: &mut App, down: bool) {
+    let len = app.details.as_ref().map_or(0, |d| d.peers.len());
+    if len > 0 {
+        let i = app.peers_state.selected().unwrap_or(0).min(len - 1);
+        app.peers_state
+            .select(Some(if down { (i + 1) % len } else { (i + len - 1) % len }));
+    }
+}
+
+pub fn sorted_peers(details: &TorrentDetails, column: PeerColumn, asc: bool) -> Vec<&Peer> {
+    let mut peers: Vec<&Peer> = details.peers.iter().collect();
+    peers.sort_by(|a, b| {
+        let ord = match column {
+            PeerColumn::Address => a.address.cmp(&b.address),
+            PeerColumn::Client => a.client_name.cmp(&b.client_name),
+            PeerColumn::Progress => a.progress.total_cmp(&b.progress),
+            PeerColumn::Download => a.rate_to_client.cmp(&b.rate_to_client),
+            PeerColumn::Upload => a.rate_to_peer.cmp(&b.rate_to_peer),
+        };
+        if asc {
+            ord
+        } else {
+            ord.reverse()
+        }
+    });
+    peers
+}
+
+fn send_torrent_set(app: &mut App, sender: &Sender<TorrentCmd>, torrent_set: TorrentSet) {
+    send_cmd(app, sender, TorrentCmd::Set(Box::new(torrent_set)));
+}
+
+// commands with an operation get an id, the processor answers with Done or Failed
+fn send_cmd(app: &mut App, sender: &Sender<TorrentCmd>, cmd: TorrentCmd) {
+    let cmd = match cmd.operation() {
+        Some(op) => {
+            app.last_cmd_id += 1;
+            app.pending.insert(app.last_cmd_id, op);
+            TorrentCmd::Tracked(app.last_cmd_id, Box::new(cmd))
+        }
+        None => cmd,
+    };
+    sender.blocking_send(cmd).expect("should send");
+}
+
+// names of the torrents an operation is about, for the status bar and the event log
+pub fn describe_torrents(app: &App, ids: &[i64]) -> String {
+    let names: Vec<&str> = ids
+        .iter()
+        .filter_map(|id| app.torrents.get(id))
+        .map(|x| x.name.as_str())
+        .take(3)
+        .collect();
+    match ids.len() {
+        0 => String::new(),
+        1 if names.len() == 1 => names[0].to_string(),
+        n if names.len() == n => format!("{} torrents: {}", n, names.join(", ")),
+        n if names.is_empty() => format!("{} torrents", n),
+        n => format!("{} torrents: {}, ...", n, names.join(", ")),
+    }
+}
+
+pub fn settings_values<'a>(app: &'a App) -> Option<&'a serde_json::Map<String, serde_json::Value>> {
+    match app.settings_target {
+        SettingsTarget::Session => app.session.as_ref().map(|x| &x.settings),
+        SettingsTarget::Torrents(_) if !app.torrent_settings.is_empty() => Some(&app.torrent_settings),
+        SettingsTarget::Torrents(_) => None,
+    }
+}
+
+fn selected_setting(app: &App) -> Option<&'static Setting> {
+    app.settings_state
+        .selected()
+        .and_then(|i| app.settings_target.settings().get(i))
+        .filter(|x| x.kind != SettingKind::Section)
+}
+
+// section headers are skipped
+fn move_setting(app: &mut App, down: bool) {
+    let settings = app.settings_target.settings();
+    let len = settings.len();
+    let mut i = app.settings_state.selected().unwrap_or(0);
+    for _ in 0..len {
+        i = if down { (i + 1) % len } else { (i + len - 1) % len };
+        if settings[i].kind != SettingKind::Section {
+            break;
+        }
+    }
+    app.settings_state.select(Some(i));
+}
+
+fn move_log(app: &mut App, down: bool) {
+    let len = app.events.len();
+    if len == 0 {
+        return;
+    }
+    let i = app.log_state.selected().unwrap_or(0);
+    let i = if down { (i + 1).min(len - 1) } else { i.saturating_sub(1) };
+    app.log_state.select(Some(i));
+}
+
+// session settings are saved one by one, torrent settings are collected and sent with a single torrent-set
+fn save_setting(app: &mut App, setting: &Setting, value: serde_json::Value, sender: &Sender<TorrentCmd>) {
+    match app.settings_target {
+        SettingsTarget::Session => {
+            // show the new value right away, the session is re-read after the update anyway
+            if let Some(session) = app.session.as_mut() {
+                session.settings.insert(setting.key.to_string(), value.clone());
+            }
+            let mut settings = serde_json::Map::new();
+            settings.insert(setting.key.to_string(), value);
+            send_cmd(app, sender, TorrentCmd::SetSession(settings));
+        }
+        SettingsTarget::Torrents(_) => {
+            app.torrent_settings.insert(setting.key.to_string(), value.clone());
+            app.settings_changed.insert(setting.key.to_string(), value);
+        }
+    }
+}
+
+// the tab keys pick a tab, next-tab and prev-tab cycle through them
+fn details_tab_key(app: &App, event: &KeyEvent) -> Option<DetailsTab> {
+    let mode = Mode::of(&app.transition).filter(|x| matches!(x, Mode::Details | Mode::Files | Mode::Peers | Mode::Trackers))?;
+    let current = DETAILS_TABS.iter().position(|x| Some(*x) == app.transition.details_tab())?;
+    let idx = match app.keymap.action(mode, event) {
+        Some(KeyAction::Tab(i)) => i,
+        Some(KeyAction::NextTab) => (current + 1) % DETAILS_TABS.len(),
+        Some(KeyAction::PrevTab) => (current + DETAILS_TABS.len() - 1) % DETAILS_TABS.len(),
+        _ => return None,
+    };
+    DETAILS_TABS.get(idx).copied()
+}
+
+// the processor asks for the fields of the new tab right away
+fn open_tab(app: &mut App, tab: DetailsTab, sender: &Sender<TorrentCmd>) {
+    app.transition = match tab {
+        DetailsTab::General => Transition::General,
+        DetailsTab::Files => Transition::Files,
+        DetailsTab::Peers => Transition::Peers,
+        DetailsTab::Trackers => Transition::Trackers,
+        DetailsTab::Pieces => Transition::Pieces,
+    };
+    match tab {
+        DetailsTab::Peers => app.peers_state.select(Some(0)),
+        DetailsTab::Trackers => app.trackers_state.select(Some(0)),
+        _ => {}
+    }
+    app.details_tab = tab;
+    let _ = sender.blocking_send(TorrentCmd::SelectTab(tab));
+}
+
+// the files tab as sorted and filtered by the user
+fn file_nodes(app: &App) -> Vec<FileNode> {
+    let mut nodes = app.details.as_ref().map(build_file_nodes).unwrap_or_default();
+    if !app.file_filter.is_empty() {
+        nodes = filter_file_nodes(nodes, &app.file_filter);
+    }
+    sort_file_nodes(&mut nodes, app.file_sort, app.file_sort_reverse);
+    nodes
+}
+
+// tree identifiers are positions, so the index is rebuilt along with the items, and the cursor and the opened
+// folders follow their nodes when sorting by completion moves them
+fn rebuild_file_tree(app: &mut App) -> Vec<FileNode> {
+    let key = |identifier: &Vec<usize>, index: &[FileIdx]| {
+        find_file_position(identifier, index).map(|idx| (identifier.len(), idx))
+    };
+    let selected = key(&app.tree_state.selected(), &app.tree_index);
+    let opened: Vec<_> = app
+        .tree_state
+        .get_all_opened()
+        .iter()
+        .filter_map(|x| key(x, &app.tree_index))
+        .collect();
+
+    let nodes = file_nodes(app);
+    app.tree_items = file_tree_items(&nodes, app.config.show_icons);
+    app.tree_index = file_tree_index(&nodes);
+
+    let identifiers = file_tree_identifiers(&app.tree_index);
+    app.tree_state.close_all();
+    for x in opened.iter().filter_map(|x| identifiers.get(x)) {
+        app.tree_state.open(x.clone());
+    }
+    app.tree_state.select(selected.and_then(|x| identifiers.get(&x).cloned()).unwrap_or_default());
+    nodes
+}
+
+// after the order changed: back to the top, with every match in sight while filtering
+fn reset_file_tree(app: &mut App) {
+    let nodes = rebuild_file_tree(app);
+    app.tree_state = TreeState::default();
+    if app.file_filter.is_empty() {
+        open_first_level(app);
+    } else {
+        for x in file_tree_folders(&nodes, &[]) {
+            app.tree_state.open(x);
+        }
+    }
+    move_up_down(app, true);
+}
+
 fn open_first_level(app: &mut App) {
     let visible = flatten(&app.tree_state.get_all_opened(), &app.tree_items);
     for x in visible {
@@ -1012,6 +2300,14 @@ fn move_up_down(app: &mut App, down: bool) {
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // initialize config early, so if there's any serious error we don't mess with the terminal
     let config = config::get_or_create_config()?;
+    let keymap = Keymap::new(&config.keys)?;
+    keymap.check_shortcuts(Mode::Action, "actions", &config.actions)?;
+    keymap.check_shortcuts(Mode::FileAction, "file-actions", &config.file_actions)?;
+    let views = config
+        .views
+        .iter()
+        .map(|x| Query::parse(&x.query).map_err(|e| format!("view \"{}\": {}", x.name, e)))
+        .collect::<Result<Vec<_>, _>>()?;
 
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -1021,7 +2317,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let (mut processor, rx) = command_processor::CommandProcessor::create();
 
-    let app = App::new(config);
+    let app = App::new(config, keymap, views);
     processor.run(app.config.clone(), app.connection_idx);
     run_app(&mut terminal, app, rx, processor.get_sender())?;
 
@@ -1032,14 +2328,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn most_recent_items(torrents: &HashMap<i64, TorrentInfo>) -> Vec<TorrentInfo> {
+fn most_recent_items(torrents: Vec<TorrentInfo>, count: usize, days: Option<u64>, now: i64) -> Vec<TorrentInfo> {
     let mut heap =
-        BinaryHeap::with_capacity_by(120, |a: &TorrentInfo, b: &TorrentInfo| b.added_date.cmp(&a.added_date));
-    for x in torrents.values() {
-        if heap.len() > 120 {
+        BinaryHeap::with_capacity_by(count + 1, |a: &TorrentInfo, b: &TorrentInfo| b.added_date.cmp(&a.added_date));
+    let oldest = days.map_or(i64::MIN, |d| now - d as i64 * 86400);
+    for x in torrents.into_iter().filter(|x| x.added_date >= oldest) {
+        heap.push(x);
+        if heap.len() > count {
             heap.pop();
         }
-        heap.push(x.clone());
     }
     heap.into_sorted_vec()
 }
@@ -0,0 +1,390 @@
+use regex::{Regex, RegexBuilder};
+
+use crate::transmission::{TorrentInfo, TorrentStatus};
+
+// search queries: `status:seeding dir:movies size>4GiB ratio<1 added<7d name~/regex/ err:tracker`,
+// words without a field match the name, `-` in front negates a term
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    terms: Vec<(bool, Term)>, // negated, term
+}
+
+#[derive(Debug, Clone)]
+enum Term {
+    Text(String),
+    Status(Vec<TorrentStatus>),
+    Flag(Flag),
+    Name(Pattern),
+    Dir(Pattern),
+    Label(Pattern),
+    Error(Pattern),
+    Size(Cmp, f64),
+    Up(Cmp, f64),
+    Down(Cmp, f64),
+    Ratio(Cmp, f64),
+    Done(Cmp, f64),
+    Peers(Cmp, f64),
+    Added(Cmp, i64), // age in seconds
+}
+
+// `is:` values that aren't statuses
+#[derive(Debug, Clone, Copy)]
+enum Flag {
+    Stalled,
+    Private,
+    Incomplete,
+    AwaitingMetadata,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    Contains(String), // lowercase
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Cmp {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+impl Cmp {
+    fn test<T: PartialOrd>(self, a: T, b: T) -> bool {
+        match self {
+            Cmp::Lt => a < b,
+            Cmp::Le => a <= b,
+            Cmp::Eq => a == b,
+            Cmp::Ge => a >= b,
+            Cmp::Gt => a > b,
+        }
+    }
+}
+
+impl Pattern {
+    fn matches(&self, s: &str) -> bool {
+        match self {
+            Pattern::Contains(text) => s.to_lowercase().contains(text),
+            Pattern::Regex(re) => re.is_match(s),
+        }
+    }
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Query, String> {
+        let mut terms = vec![];
+        for (token, quoted) in tokenize(input)? {
+            if quoted {
+                terms.push((false, Term::Text(token.to_lowercase())));
+            } else if let Some(rest) = token.strip_prefix('-').filter(|x| !x.is_empty()) {
+                terms.push((true, parse_term(rest)?));
+            } else {
+                terms.push((false, parse_term(&token)?));
+            }
+        }
+        Ok(Query { terms })
+    }
+
+    // `now` is a unix timestamp, for `added`
+    pub fn matches(&self, x: &TorrentInfo, now: i64) -> bool {
+        self.terms.iter().all(|(negated, term)| term.matches(x, now) != *negated)
+    }
+}
+
+impl Term {
+    fn matches(&self, x: &TorrentInfo, now: i64) -> bool {
+        match self {
+            Term::Text(text) => x.name.to_lowercase().contains(text),
+            Term::Status(xs) => xs.contains(&x.status),
+            Term::Flag(Flag::Stalled) => x.is_stalled,
+            Term::Flag(Flag::Private) => x.is_private,
+            Term::Flag(Flag::Incomplete) => x.percent_done < 1.0,
+            Term::Flag(Flag::AwaitingMetadata) => x.metadata_percent_complete < 1.0,
+            Term::Name(p) => p.matches(&x.name),
+            Term::Dir(p) => p.matches(&x.download_dir),
+            Term::Label(p) => x.labels.iter().any(|l| p.matches(l)),
+            Term::Error(p) => x.error != 0 && p.matches(&x.error_string),
+            Term::Size(cmp, v) => cmp.test(x.size_when_done as f64, *v),
+            Term::Up(cmp, v) => cmp.test(x.rate_upload as f64, *v),
+            Term::Down(cmp, v) => cmp.test(x.rate_download as f64, *v),
+            Term::Ratio(cmp, v) => cmp.test(x.upload_ratio, *v),
+            Term::Done(cmp, v) => cmp.test(x.percent_done * 100.0, *v),
+            Term::Peers(cmp, v) => cmp.test(x.peers_connected as f64, *v),
+            Term::Added(cmp, age) => cmp.test(now - x.added_date, *age),
+        }
+    }
+}
+
+// splits on whitespace, keeping "quoted text" and /regexes/ together
+fn tokenize(input: &str) -> Result<Vec<(String, bool)>, String> {
+    let mut tokens = vec![];
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut token = String::new();
+        let quoted = c == '"';
+        if quoted {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => token.push(c),
+                    None => return Err("missing closing \"".to_string()),
+                }
+            }
+            tokens.push((token, true));
+            continue;
+        }
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                break;
+            }
+            chars.next();
+            token.push(c);
+            if c == '~' && chars.peek() == Some(&'/') {
+                token.push(chars.next().unwrap());
+                let mut escaped = false;
+                loop {
+                    match chars.next() {
+                        Some('/') if !escaped => break,
+                        Some(c) => {
+                            escaped = c == '\\' && !escaped;
+                            token.push(c);
+                        }
+                        None => return Err("missing closing / of the regex".to_string()),
+                    }
+                }
+                token.push('/');
+            }
+        }
+        tokens.push((token, false));
+    }
+    Ok(tokens)
+}
+
+fn parse_term(token: &str) -> Result<Term, String> {
+    let key_len = token.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(token.len());
+    let (key, rest) = token.split_at(key_len);
+    let op_len = rest.find(|c: char| !"<>=:~".contains(c)).unwrap_or(rest.len());
+    let (op, value) = rest.split_at(op_len);
+    if key.is_empty() || op.is_empty() {
+        return Ok(Term::Text(token.to_lowercase()));
+    }
+    // anything that isn't a field, like `Wars: Episode`, is searched for in the name
+    match key.to_lowercase().as_str() {
+        "is" if op == ":" => parse_flag(value).map(Term::Flag).or_else(|_| parse_status(value).map(Term::Status)),
+        "status" if op == ":" => parse_status(value).map(Term::Status),
+        "name" => parse_pattern(key, op, value).map(Term::Name),
+        "dir" => parse_pattern(key, op, value).map(Term::Dir),
+        "label" => parse_pattern(key, op, value).map(Term::Label),
+        "err" | "error" => parse_pattern(key, op, value).map(Term::Error),
+        "size" => Ok(Term::Size(parse_cmp(key, op)?, parse_size(value)?)),
+        "up" => Ok(Term::Up(parse_cmp(key, op)?, parse_size(value)?)),
+        "down" => Ok(Term::Down(parse_cmp(key, op)?, parse_size(value)?)),
+        "ratio" => Ok(Term::Ratio(parse_cmp(key, op)?, parse_number(value)?)),
+        "done" => Ok(Term::Done(parse_cmp(key, op)?, parse_number(value.trim_end_matches('%'))?)),
+        "peers" => Ok(Term::Peers(parse_cmp(key, op)?, parse_number(value)?)),
+        "added" => Ok(Term::Added(parse_cmp(key, op)?, parse_age(value)?)),
+        "status" | "is" => Err(format!("use {}:<status>", key)),
+        _ => Ok(Term::Text(token.to_lowercase())),
+    }
+}
+
+fn parse_cmp(key: &str, op: &str) -> Result<Cmp, String> {
+    match op {
+        "<" => Ok(Cmp::Lt),
+        "<=" => Ok(Cmp::Le),
+        "=" | ":" => Ok(Cmp::Eq),
+        ">=" => Ok(Cmp::Ge),
+        ">" => Ok(Cmp::Gt),
+        _ => Err(format!("'{}' compares with <, <=, =, >= or >", key)),
+    }
+}
+
+fn parse_pattern(key: &str, op: &str, value: &str) -> Result<Pattern, String> {
+    match op {
+        ":" => Ok(Pattern::Contains(value.to_lowercase())),
+        "~" => {
+            // `/.../i` ignores case
+            let (body, ignore_case) = if let Some(body) = value.strip_suffix("/i") {
+                (body, true)
+            } else {
+                (value.strip_suffix('/').unwrap_or(value), false)
+            };
+            let body = body.strip_prefix('/').ok_or_else(|| format!("use {}~/regex/", key))?;
+            RegexBuilder::new(body)
+                .case_insensitive(ignore_case)
+                .build()
+                .map(Pattern::Regex)
+                .map_err(|e| {
+                    let message = e.to_string();
+                    let reason = message.lines().last().unwrap_or_default().trim_start_matches("error: ");
+                    format!("bad regex: {}", reason)
+                })
+        }
+        _ => Err(format!("use {}:text or {}~/regex/", key, key)),
+    }
+}
+
+fn parse_status(value: &str) -> Result<Vec<TorrentStatus>, String> {
+    let xs = match value.to_lowercase().as_str() {
+        "paused" | "stopped" => vec![TorrentStatus::Paused],
+        "checking" | "verifying" => vec![TorrentStatus::VerifyQueued, TorrentStatus::Verifying],
+        "queued" => vec![TorrentStatus::DownQueued, TorrentStatus::SeedQueued],
+        "downloading" => vec![TorrentStatus::Downloading],
+        "seeding" => vec![TorrentStatus::Seeding],
+        _ => return Err(format!("unknown status '{}'", value)),
+    };
+    Ok(xs)
+}
+
+fn parse_flag(value: &str) -> Result<Flag, String> {
+    match value.to_lowercase().as_str() {
+        "stalled" => Ok(Flag::Stalled),
+        "private" => Ok(Flag::Private),
+        "incomplete" => Ok(Flag::Incomplete),
+        "magnet" | "metadata" => Ok(Flag::AwaitingMetadata),
+        _ => Err(format!("unknown flag '{}'", value)),
+    }
+}
+
+fn parse_number(value: &str) -> Result<f64, String> {
+    value.parse::<f64>().map_err(|_| format!("'{}' is not a number", value))
+}
+
+// 4GiB, 700M, 1.5TB; single letters are binary units like the ones in the table
+fn parse_size(value: &str) -> Result<f64, String> {
+    let idx = value.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(idx);
+    let multiplier = match unit.to_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "k" | "kib" => 1024.0,
+        "m" | "mib" => 1024.0 * 1024.0,
+        "g" | "gib" => 1024.0 * 1024.0 * 1024.0,
+        "t" | "tib" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        "kb" => 1e3,
+        "mb" => 1e6,
+        "gb" => 1e9,
+        "tb" => 1e12,
+        _ => return Err(format!("unknown size unit '{}'", unit)),
+    };
+    Ok(parse_number(number)? * multiplier)
+}
+
+// 30m, 12h, 7d, 2w
+fn parse_age(value: &str) -> Result<i64, String> {
+    let idx = value.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(idx);
+    let seconds = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "" | "d" => 86400,
+        "w" => 7 * 86400,
+        _ => return Err(format!("unknown time unit '{}', use s, m, h, d or w", unit)),
+    };
+    Ok((parse_number(number)? * seconds as f64) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn torrent() -> TorrentInfo {
+        TorrentInfo {
+            id: 1,
+            name: "Star Wars: Episode IV".to_string(),
+            status: TorrentStatus::Seeding,
+            percent_done: 1.0,
+            error: 0,
+            error_string: String::new(),
+            eta: -1,
+            queue_position: 0,
+            is_finished: false,
+            is_stalled: false,
+            metadata_percent_complete: 1.0,
+            peers_connected: 3,
+            rate_download: 0,
+            rate_upload: 2048,
+            recheck_progress: 0.0,
+            size_when_done: 5 * 1024 * 1024 * 1024,
+            download_dir: "/data/movies".to_string(),
+            uploaded_ever: 0,
+            upload_ratio: 0.5,
+            added_date: 1000,
+            labels: vec!["scifi".to_string()],
+            downloaded_ever: 0,
+            bandwidth_priority: 0,
+            is_private: true,
+            done_date: 0,
+        }
+    }
+
+    fn matches(query: &str) -> bool {
+        Query::parse(query).unwrap().matches(&torrent(), 1000 + 3 * 86400)
+    }
+
+    #[test]
+    fn tokenize_quotes_and_regexes() {
+        let tokens = tokenize(r#"  "star wars"  name~/a b\/c/i -x "#).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                ("star wars".to_string(), true),
+                (r"name~/a b\/c/i".to_string(), false),
+                ("-x".to_string(), false),
+            ]
+        );
+        assert!(tokenize(r#"size>1G "star"#).is_err());
+        assert!(tokenize("name~/star").is_err());
+    }
+
+    #[test]
+    fn sizes_and_ages() {
+        assert_eq!(parse_size("700"), Ok(700.0));
+        assert_eq!(parse_size("1.5k"), Ok(1536.0));
+        assert_eq!(parse_size("4GiB"), Ok(4.0 * 1024.0 * 1024.0 * 1024.0));
+        assert_eq!(parse_size("2MB"), Ok(2e6));
+        assert!(parse_size("3x").is_err());
+        assert_eq!(parse_age("30m"), Ok(1800));
+        assert_eq!(parse_age("12h"), Ok(12 * 3600));
+        assert_eq!(parse_age("7"), Ok(7 * 86400));
+        assert_eq!(parse_age("2w"), Ok(14 * 86400));
+        assert!(parse_age("1y").is_err());
+    }
+
+    #[test]
+    fn negation() {
+        assert!(matches("status:seeding"));
+        assert!(!matches("-status:seeding"));
+        assert!(matches("-label:music"));
+        assert!(!matches("-wars"));
+        // a lone dash is text
+        assert!(!matches("-"));
+    }
+
+    #[test]
+    fn fields() {
+        assert!(matches("Status:Seeding is:private dir:movies label:sci"));
+        assert!(matches("size>4GiB size<6G ratio<1 done=100% peers>=3 up>1k"));
+        assert!(matches("added<7d added>2d"));
+        assert!(!matches("added<1d"));
+        assert!(matches(r"name~/^star wars/i"));
+        assert!(!matches(r"name~/^star wars/"));
+        assert!(!matches("err:tracker"));
+        assert!(Query::parse("size~4G").is_err());
+        assert!(Query::parse("status:sleeping").is_err());
+    }
+
+    #[test]
+    fn unknown_fields_are_text() {
+        assert!(matches("Wars: Episode"));
+        assert!(matches(r#""wars: episode""#));
+        assert!(!matches("S01E02-x:y"));
+    }
+}
@@ -0,0 +1,226 @@
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingKind {
+    Section,
+    Bool,
+    Int,
+    Float,
+    Text,
+    Choice(&'static [&'static str]),
+    Mode(&'static [&'static str]), // value is the index of the name
+    Priority,
+    TimeOfDay, // minutes since midnight
+    Days,      // transmission's day bitfield, sunday is the lowest bit
+}
+
+#[derive(Debug, Clone)]
+pub struct Setting {
+    pub key: &'static str,
+    pub label: &'static str,
+    pub kind: SettingKind,
+}
+
+const fn setting(key: &'static str, label: &'static str, kind: SettingKind) -> Setting {
+    Setting { key, label, kind }
+}
+
+const fn section(label: &'static str) -> Setting {
+    Setting {
+        key: "",
+        label,
+        kind: SettingKind::Section,
+    }
+}
+
+pub static SESSION_SETTINGS: &[Setting] = &[
+    section("Speed Limits"),
+    setting("speed-limit-down-enabled", "Limit download", SettingKind::Bool),
+    setting("speed-limit-down", "Download limit (kB/s)", SettingKind::Int),
+    setting("speed-limit-up-enabled", "Limit upload", SettingKind::Bool),
+    setting("speed-limit-up", "Upload limit (kB/s)", SettingKind::Int),
+    section("Turtle Mode"),
+    setting("alt-speed-enabled", "Turtle mode enabled", SettingKind::Bool),
+    setting("alt-speed-down", "Turtle download (kB/s)", SettingKind::Int),
+    setting("alt-speed-up", "Turtle upload (kB/s)", SettingKind::Int),
+    setting("alt-speed-time-enabled", "Scheduled", SettingKind::Bool),
+    setting("alt-speed-time-begin", "From", SettingKind::TimeOfDay),
+    setting("alt-speed-time-end", "To", SettingKind::TimeOfDay),
+    setting("alt-speed-time-day", "On days", SettingKind::Days),
+    section("Queue"),
+    setting("download-queue-enabled", "Limit downloads", SettingKind::Bool),
+    setting("download-queue-size", "Active downloads", SettingKind::Int),
+    setting("seed-queue-enabled", "Limit seeding", SettingKind::Bool),
+    setting("seed-queue-size", "Active seeds", SettingKind::Int),
+    setting("queue-stalled-enabled", "Skip stalled", SettingKind::Bool),
+    setting("queue-stalled-minutes", "Stalled after (min)", SettingKind::Int),
+    section("Seeding"),
+    setting("seedRatioLimited", "Stop at ratio", SettingKind::Bool),
+    setting("seedRatioLimit", "Ratio", SettingKind::Float),
+    setting("idle-seeding-limit-enabled", "Stop when idle", SettingKind::Bool),
+    setting("idle-seeding-limit", "Idle for (min)", SettingKind::Int),
+    section("Peers"),
+    setting("peer-limit-global", "Max peers overall", SettingKind::Int),
+    setting("peer-limit-per-torrent", "Max peers per torrent", SettingKind::Int),
+    setting("peer-port", "Peer port", SettingKind::Int),
+    setting("port-forwarding-enabled", "Port forwarding", SettingKind::Bool),
+    setting(
+        "encryption",
+        "Encryption",
+        SettingKind::Choice(&["required", "preferred", "tolerated"]),
+    ),
+    setting("dht-enabled", "DHT", SettingKind::Bool),
+    setting("pex-enabled", "PEX", SettingKind::Bool),
+    setting("lpd-enabled", "Local peer discovery", SettingKind::Bool),
+    setting("utp-enabled", "uTP", SettingKind::Bool),
+    section("Files"),
+    setting("incomplete-dir-enabled", "Use incomplete dir", SettingKind::Bool),
+    setting("incomplete-dir", "Incomplete dir", SettingKind::Text),
+    setting("rename-partial-files", "Append .part", SettingKind::Bool),
+    setting("start-added-torrents", "Start added torrents", SettingKind::Bool),
+];
+
+pub static TORRENT_SETTINGS: &[Setting] = &[
+    section("Bandwidth"),
+    setting("honorsSessionLimits", "Honor session limits", SettingKind::Bool),
+    setting("downloadLimited", "Limit download", SettingKind::Bool),
+    setting("downloadLimit", "Download limit (kB/s)", SettingKind::Int),
+    setting("uploadLimited", "Limit upload", SettingKind::Bool),
+    setting("uploadLimit", "Upload limit (kB/s)", SettingKind::Int),
+    setting("bandwidthPriority", "Priority", SettingKind::Priority),
+    setting("peer-limit", "Max peers", SettingKind::Int),
+    section("Seeding"),
+    setting("seedRatioMode", "Ratio limit", SettingKind::Mode(&SEED_MODES)),
+    setting("seedRatioLimit", "Ratio", SettingKind::Float),
+    setting("seedIdleMode", "Idle limit", SettingKind::Mode(&SEED_MODES)),
+    setting("seedIdleLimit", "Idle for (min)", SettingKind::Int),
+];
+
+const SEED_MODES: [&str; 3] = ["global", "custom", "unlimited"];
+pub const PRIORITIES: [&str; 3] = ["low", "normal", "high"];
+const DAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const ALL_DAYS: i64 = 127;
+const WEEKDAYS: i64 = 62;
+const WEEKEND: i64 = 65;
+
+pub fn format_value(setting: &Setting, value: Option<&Value>) -> String {
+    match (&setting.kind, value) {
+        (SettingKind::Section, _) => "".to_string(),
+        (_, None) => "n/a".to_string(),
+        (SettingKind::Bool, Some(v)) => if v.as_bool().unwrap_or(false) { "[x]" } else { "[ ]" }.to_string(),
+        (SettingKind::Float, Some(v)) => format!("{:.2}", v.as_f64().unwrap_or(0.0)),
+        (SettingKind::Mode(modes), Some(v)) => v
+            .as_u64()
+            .and_then(|i| modes.get(i as usize))
+            .map_or_else(|| v.to_string(), |x| x.to_string()),
+        (SettingKind::Priority, Some(v)) => v
+            .as_i64()
+            .and_then(|i| PRIORITIES.get((i + 1) as usize))
+            .map_or_else(|| v.to_string(), |x| x.to_string()),
+        (SettingKind::TimeOfDay, Some(v)) => {
+            let minutes = v.as_i64().unwrap_or(0);
+            format!("{:02}:{:02}", minutes / 60, minutes % 60)
+        }
+        (SettingKind::Days, Some(v)) => match v.as_i64().unwrap_or(0) {
+            ALL_DAYS => "every day".to_string(),
+            WEEKDAYS => "weekdays".to_string(),
+            WEEKEND => "weekend".to_string(),
+            days => DAYS
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| days & (1 << i) != 0)
+                .map(|(_, d)| *d)
+                .collect::<Vec<_>>()
+                .join(", "),
+        },
+        (_, Some(Value::String(s))) => s.clone(),
+        (_, Some(v)) => v.to_string(),
+    }
+}
+
+// value as it's shown in the edit line
+pub fn edit_value(setting: &Setting, value: Option<&Value>) -> String {
+    match (&setting.kind, value) {
+        (SettingKind::Text | SettingKind::Choice(_), Some(Value::String(s))) => s.clone(),
+        (_, None) => "".to_string(),
+        _ => format_value(setting, value),
+    }
+}
+
+pub fn parse_value(setting: &Setting, input: &str) -> Result<Value, String> {
+    let input = input.trim();
+    match &setting.kind {
+        SettingKind::Section => Err("not editable".to_string()),
+        SettingKind::Bool => match input {
+            "yes" | "true" | "on" | "1" | "[x]" => Ok(json!(true)),
+            "no" | "false" | "off" | "0" | "[ ]" => Ok(json!(false)),
+            _ => Err("expected yes or no".to_string()),
+        },
+        SettingKind::Int => input
+            .parse::<i64>()
+            .map(|x| json!(x))
+            .map_err(|_| "expected a whole number".to_string()),
+        SettingKind::Float => input
+            .parse::<f64>()
+            .map(|x| json!(x))
+            .map_err(|_| "expected a number".to_string()),
+        SettingKind::Text => Ok(json!(input)),
+        SettingKind::Choice(choices) => choices
+            .iter()
+            .find(|x| **x == input)
+            .map(|x| json!(x))
+            .ok_or_else(|| format!("expected one of: {}", choices.join(", "))),
+        SettingKind::Mode(modes) => modes
+            .iter()
+            .position(|x| *x == input)
+            .map(|x| json!(x))
+            .ok_or_else(|| format!("expected one of: {}", modes.join(", "))),
+        SettingKind::Priority => PRIORITIES
+            .iter()
+            .position(|x| *x == input)
+            .map(|x| json!(x as i64 - 1))
+            .ok_or_else(|| format!("expected one of: {}", PRIORITIES.join(", "))),
+        SettingKind::TimeOfDay => input
+            .split_once(':')
+            .and_then(|(h, m)| Some((h.parse::<i64>().ok()?, m.parse::<i64>().ok()?)))
+            .filter(|(h, m)| (0..24).contains(h) && (0..60).contains(m))
+            .map(|(h, m)| json!(h * 60 + m))
+            .ok_or_else(|| "expected time as HH:MM".to_string()),
+        SettingKind::Days => match input {
+            "every day" | "all" => Ok(json!(ALL_DAYS)),
+            "weekdays" => Ok(json!(WEEKDAYS)),
+            "weekend" => Ok(json!(WEEKEND)),
+            _ => input
+                .split(',')
+                .try_fold(0, |acc, d| {
+                    DAYS.iter()
+                        .position(|x| x.eq_ignore_ascii_case(d.trim()))
+                        .map(|i| acc | (1 << i))
+                        .ok_or_else(|| format!("unknown day '{}'", d.trim()))
+                })
+                .map(|x: i64| json!(x)),
+        },
+    }
+}
+
+// bools and choices are switched in place, without the edit line
+pub fn toggle_value(setting: &Setting, value: Option<&Value>) -> Option<Value> {
+    match (&setting.kind, value) {
+        (SettingKind::Bool, Some(v)) => Some(json!(!v.as_bool().unwrap_or(false))),
+        (SettingKind::Choice(choices), Some(v)) => {
+            let current = choices.iter().position(|x| Some(*x) == v.as_str()).unwrap_or(0);
+            Some(json!(choices[(current + 1) % choices.len()]))
+        }
+        (SettingKind::Mode(modes), Some(v)) => Some(json!((v.as_u64().unwrap_or(0) + 1) % modes.len() as u64)),
+        (SettingKind::Priority, Some(v)) => Some(json!((v.as_i64().unwrap_or(0) + 2) % 3 - 1)),
+        _ => None,
+    }
+}
+
+pub fn setting_keys(settings: &[Setting]) -> Vec<&'static str> {
+    settings
+        .iter()
+        .filter(|x| x.kind != SettingKind::Section)
+        .map(|x| x.key)
+        .collect()
+}
@@ -1,5 +1,7 @@
+use crate::query::Query;
 use crate::transmission::{TorrentInfo, TorrentStatus};
-use std::collections::HashMap;
+use crate::utils::unix_now;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 #[derive(Debug)]
 pub struct TorrentGroupStats {
@@ -12,7 +14,16 @@ pub struct TorrentGroupStats {
     pub num_stopped: u64,
     pub num_queue_checking: u64,
     pub num_error: u64,
+    pub num_stalled: u64,
+    pub num_finished: u64, // recently, see `finished-days`
+    pub num_incomplete: u64,
+    pub num_private: u64,
+    pub num_awaiting_metadata: u64,
     pub folders: HashMap<String, u64>,
+    pub folder_sizes: HashMap<String, i64>,
+    pub folder_tree: Vec<FolderNode>,
+    pub labels: HashMap<String, u64>,
+    pub views: Vec<u64>, // in the config order
 }
 
 impl TorrentGroupStats {
@@ -27,20 +38,61 @@ impl TorrentGroupStats {
             num_stopped: 0,
             num_seeding: 0,
             num_error: 0,
+            num_stalled: 0,
+            num_finished: 0,
+            num_incomplete: 0,
+            num_private: 0,
+            num_awaiting_metadata: 0,
             folders: HashMap::new(),
+            folder_sizes: HashMap::new(),
+            folder_tree: vec![],
+            labels: HashMap::new(),
+            views: vec![],
         }
     }
 }
 
-pub fn update_torrent_stats(torrents: &HashMap<i64, TorrentInfo>) -> TorrentGroupStats {
+// `finished_within` is in seconds, `base_dir` is the default download dir of the connection
+pub fn update_torrent_stats(
+    torrents: &HashMap<i64, TorrentInfo>,
+    views: &[Query],
+    finished_within: i64,
+    base_dir: &str,
+) -> TorrentGroupStats {
     let mut group_stats = TorrentGroupStats::empty();
+    group_stats.views = vec![0; views.len()];
+    let now = unix_now();
     for x in torrents.values() {
+        for (i, view) in views.iter().enumerate() {
+            if view.matches(x, now) {
+                group_stats.views[i] += 1;
+            }
+        }
         let error = x.error;
         if error != 0 {
             group_stats.num_error += 1;
         }
+        if x.is_stalled {
+            group_stats.num_stalled += 1;
+        }
+        if is_recently_finished(x, now, finished_within) {
+            group_stats.num_finished += 1;
+        }
+        if x.percent_done < 1.0 {
+            group_stats.num_incomplete += 1;
+        }
+        if x.is_private {
+            group_stats.num_private += 1;
+        }
+        if x.metadata_percent_complete < 1.0 {
+            group_stats.num_awaiting_metadata += 1;
+        }
         let folder = x.download_dir.clone();
-        *group_stats.folders.entry(folder).or_insert(0) += 1;
+        *group_stats.folders.entry(folder.clone()).or_insert(0) += 1;
+        *group_stats.folder_sizes.entry(folder).or_insert(0) += x.size_when_done;
+        for label in &x.labels {
+            *group_stats.labels.entry(label.clone()).or_insert(0) += 1;
+        }
         group_stats.num_total += 1;
         match x.status {
             TorrentStatus::Paused => group_stats.num_stopped += 1,
@@ -52,6 +104,7 @@ pub fn update_torrent_stats(torrents: &HashMap<i64, TorrentInfo>) -> TorrentGrou
             TorrentStatus::Seeding => group_stats.num_seeding += 1,
         }
     }
+    group_stats.folder_tree = folder_tree(&group_stats.folders, &group_stats.folder_sizes, base_dir);
 
     group_stats
     /*
@@ -98,3 +151,119 @@ pub fn update_torrent_stats(torrents: &HashMap<i64, TorrentInfo>) -> TorrentGrou
     }
     */
 }
+
+// doneDate is 0 until a torrent completes
+pub fn is_recently_finished(x: &TorrentInfo, now: i64, within: i64) -> bool {
+    x.done_date > 0 && now - x.done_date <= within
+}
+
+// a download dir and everything beneath it, counts and sizes include the subfolders
+#[derive(Debug, Clone)]
+pub struct FolderNode {
+    pub path: String,
+    pub name: String, // relative to the parent
+    pub count: u64,
+    pub size: i64,
+    pub children: Vec<FolderNode>,
+}
+
+// one line of the sidebar
+#[derive(Debug, Clone)]
+pub struct FolderRow {
+    pub path: String,
+    pub name: String,
+    pub depth: usize,
+    pub count: u64,
+    pub size: i64,
+    pub has_children: bool,
+    pub expanded: bool,
+}
+
+#[derive(Default)]
+struct Trie {
+    count: u64,
+    size: i64,
+    children: BTreeMap<String, Trie>,
+}
+
+// folders that only lead to a single subfolder are merged into one node, e.g. `movies/hd`,
+// but the base dir always gets its own node
+fn folder_tree(folders: &HashMap<String, u64>, sizes: &HashMap<String, i64>, base_dir: &str) -> Vec<FolderNode> {
+    let mut root = Trie::default();
+    for (path, count) in folders {
+        let mut node = &mut root;
+        for part in path.trim_end_matches('/').split('/') {
+            node = node.children.entry(part.to_string()).or_default();
+        }
+        node.count += count;
+        node.size += sizes.get(path).unwrap_or(&0);
+    }
+    let mut nodes = vec![];
+    for (name, trie) in &root.children {
+        // absolute paths share the empty first component
+        if name.is_empty() && trie.count == 0 {
+            for (child, x) in &trie.children {
+                nodes.push(folder_node(format!("/{}", child), format!("/{}", child), x, base_dir));
+            }
+        } else {
+            nodes.push(folder_node(name.clone(), name.clone(), trie, base_dir));
+        }
+    }
+    nodes
+}
+
+fn folder_node(mut name: String, mut path: String, mut trie: &Trie, base_dir: &str) -> FolderNode {
+    while trie.count == 0 && trie.children.len() == 1 && path != base_dir.trim_end_matches('/') {
+        let (child, x) = trie.children.iter().next().expect("one child");
+        name = format!("{}/{}", name, child);
+        path = format!("{}/{}", path, child);
+        trie = x;
+    }
+    // like process_folder, the base dir goes by its last component
+    if path == base_dir.trim_end_matches('/') {
+        name = path.rsplit('/').next().unwrap_or_default().to_string();
+    }
+    let children: Vec<FolderNode> = trie
+        .children
+        .iter()
+        .map(|(child, x)| folder_node(child.clone(), format!("{}/{}", path, child), x, base_dir))
+        .collect();
+    FolderNode {
+        count: trie.count + children.iter().map(|x| x.count).sum::<u64>(),
+        size: trie.size + children.iter().map(|x| x.size).sum::<i64>(),
+        path,
+        name,
+        children,
+    }
+}
+
+// the rows of the tree with collapsed folders hiding their children
+pub fn folder_rows(tree: &[FolderNode], expanded: &HashSet<String>) -> Vec<FolderRow> {
+    let mut rows = vec![];
+    push_folder_rows(tree, expanded, 0, &mut rows);
+    rows
+}
+
+fn push_folder_rows(nodes: &[FolderNode], expanded: &HashSet<String>, depth: usize, rows: &mut Vec<FolderRow>) {
+    for x in nodes {
+        let is_expanded = expanded.contains(&x.path);
+        rows.push(FolderRow {
+            path: x.path.clone(),
+            name: x.name.clone(),
+            depth,
+            count: x.count,
+            size: x.size,
+            has_children: !x.children.is_empty(),
+            expanded: is_expanded,
+        });
+        if is_expanded {
+            push_folder_rows(&x.children, expanded, depth + 1, rows);
+        }
+    }
+}
+
+// `dir` itself and everything beneath it
+pub fn is_in_folder(download_dir: &str, dir: &str) -> bool {
+    let dir = dir.trim_end_matches('/');
+    download_dir.trim_end_matches('/') == dir || download_dir.starts_with(&format!("{}/", dir))
+}
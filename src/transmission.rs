@@ -6,6 +6,7 @@ use serde_json::Value;
 use serde::de::{self, Visitor};
 use serde::{Deserialize, Serialize};
 use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
 
 pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
@@ -35,6 +36,20 @@ impl fmt::Display for HttpError {
     }
 }
 
+// the daemon answered but refused the request, unlike errors on the way there
+#[derive(Debug, Clone)]
+pub struct RpcError {
+    pub result: String,
+}
+
+impl std::error::Error for RpcError {}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Method failed, result: '{}'", self.result)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TorrentInfo {
     pub id: i64,
@@ -57,13 +72,19 @@ pub struct TorrentInfo {
     pub uploaded_ever: i64,
     pub upload_ratio: f64,
     pub added_date: i64,
+    pub labels: Vec<String>,
+    pub downloaded_ever: i64,
+    pub bandwidth_priority: i64,
+    pub is_private: bool,
+    pub done_date: i64,
 }
 
 impl TorrentInfo {
-    pub fn new(json: &Value) -> Self {
-        Self::from_json(json).expect("get torrents response contains invalid number of fields")
+    pub fn new(header: &[Value], json: &Value) -> Self {
+        Self::from_json(header, json).expect("get torrents response contains invalid number of fields")
     }
-    pub fn from_json(json: &Value) -> Result<Self> {
+    // the first 20 fields always come in the TORRENT_INFO_FIELDS order, the rest are looked up by the header
+    pub fn from_json(header: &[Value], json: &Value) -> Result<Self> {
         let xs = json.as_array().unwrap();
         if xs.len() < 20 {
             Err(Box::new(HttpError::new("get torrents response contains invalid number of fields")))
@@ -89,10 +110,15 @@ impl TorrentInfo {
                 uploaded_ever: xs[17].as_i64().unwrap(),
                 upload_ratio: xs[18].as_f64().unwrap(),
                 added_date: xs[19].as_i64().unwrap(),
+                labels: parse_labels(optional_field(header, xs, "labels")),
+                downloaded_ever: optional_field(header, xs, "downloadedEver").and_then(|x| x.as_i64()).unwrap_or(0),
+                bandwidth_priority: optional_field(header, xs, "bandwidthPriority").and_then(|x| x.as_i64()).unwrap_or(0),
+                is_private: optional_field(header, xs, "isPrivate").and_then(|x| x.as_bool()).unwrap_or(false),
+                done_date: optional_field(header, xs, "doneDate").and_then(|x| x.as_i64()).unwrap_or(0),
             })
         }
     }
-    pub fn update(&mut self, xs: &[Value]) {
+    pub fn update(&mut self, header: &[Value], xs: &[Value]) {
         self.status = xs[2].as_i64().unwrap().try_into().unwrap();
         self.percent_done = xs[3].as_f64().unwrap();
         self.error = xs[4].as_i64().unwrap();
@@ -111,17 +137,36 @@ impl TorrentInfo {
         self.uploaded_ever = xs[17].as_i64().unwrap();
         self.upload_ratio = xs[18].as_f64().unwrap();
         self.added_date = xs[19].as_i64().unwrap();
+        self.labels = parse_labels(optional_field(header, xs, "labels"));
+        self.downloaded_ever = optional_field(header, xs, "downloadedEver").and_then(|x| x.as_i64()).unwrap_or(0);
+        self.bandwidth_priority = optional_field(header, xs, "bandwidthPriority").and_then(|x| x.as_i64()).unwrap_or(0);
+        self.is_private = optional_field(header, xs, "isPrivate").and_then(|x| x.as_bool()).unwrap_or(false);
+        self.done_date = optional_field(header, xs, "doneDate").and_then(|x| x.as_i64()).unwrap_or(0);
     }
 }
 
+// optional fields depend on the enabled columns and on the daemon version, so their position varies
+fn optional_field<'a>(header: &[Value], xs: &'a [Value], name: &str) -> Option<&'a Value> {
+    header.iter().position(|x| x.as_str() == Some(name)).and_then(|i| xs.get(i))
+}
+
+// labels appeared in transmission 3.0, older daemons just skip the column
+fn parse_labels(x: Option<&Value>) -> Vec<String> {
+    x.and_then(|x| x.as_array())
+        .map(|xs| xs.iter().filter_map(|l| l.as_str().map(String::from)).collect())
+        .unwrap_or_default()
+}
+
 pub struct TransmissionClient {
     client: reqwest::Client,
     session_id: Mutex<String>,
+    rpc_version: AtomicU64,
     url: String,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub enum TorrentStatus {
+    #[default]
     Paused = 0,
     VerifyQueued = 1,
     Verifying = 2,
@@ -223,6 +268,12 @@ pub struct Session {
     #[serde(rename = "download-dir")]
     pub download_dir: String,
     pub version: String,
+    #[serde(rename = "rpc-version")]
+    #[serde(default)]
+    pub rpc_version: u64,
+    // everything else, edited generically on the settings screen
+    #[serde(flatten)]
+    pub settings: serde_json::Map<String, Value>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -298,6 +349,36 @@ pub struct Tracker {
     pub tier: u64,
 }
 
+#[derive(Deserialize, Debug, Clone)]
+pub struct TorrentTrackers {
+    pub id: i64,
+    pub trackers: Vec<Tracker>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TrackersResponse {
+    pub torrents: Vec<TorrentTrackers>,
+}
+
+// trackerList format: one announce url per line, tiers are separated by a blank line
+pub fn format_tracker_list(trackers: &[(u64, String)]) -> String {
+    let mut tiers: Vec<u64> = trackers.iter().map(|x| x.0).collect();
+    tiers.sort_unstable();
+    tiers.dedup();
+    tiers
+        .iter()
+        .map(|tier| {
+            trackers
+                .iter()
+                .filter(|x| x.0 == *tier)
+                .map(|x| x.1.as_str())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Peer {
     pub address: String,
@@ -310,58 +391,145 @@ pub struct Peer {
     pub rate_to_peer: u64,
     #[serde(rename = "flagStr")]
     pub flag_str: String,
+    #[serde(rename = "clientIsChoked")]
+    #[serde(default)]
+    pub client_is_choked: bool,
+    #[serde(rename = "peerIsChoked")]
+    #[serde(default)]
+    pub peer_is_choked: bool,
 }
-static TORRENT_DETAILS_FIELDS: &[&str] = &[
+// the details screen tabs, each one asks only for what it shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DetailsTab {
+    #[default]
+    General,
+    Files,
+    Peers,
+    Trackers,
+    Pieces,
+}
+
+pub static DETAILS_TABS: &[DetailsTab] = &[
+    DetailsTab::General,
+    DetailsTab::Files,
+    DetailsTab::Peers,
+    DetailsTab::Trackers,
+    DetailsTab::Pieces,
+];
+
+// every tab gets these
+static DETAILS_BASE_FIELDS: &[&str] = &[
     "id",
     "name",
-    "eta",
-    "sizeWhenDone",
+    "status",
     "downloadDir",
+    "sizeWhenDone",
+    "percentDone",
+    "error",
+    "errorString",
+];
+
+static GENERAL_FIELDS: &[&str] = &[
+    "eta",
     "comment",
+    "creator",
     "hashString",
+    "isPrivate",
+    "labels",
     "rateDownload",
     "rateUpload",
     "uploadRatio",
     "seedRatioLimit",
+    "seedRatioMode",
+    "addedDate",
+    "dateCreated",
+    "startDate",
+    "activityDate",
     "doneDate",
-    "percentDone",
+    "totalSize",
+    "leftUntilDone",
+    "haveValid",
+    "haveUnchecked",
     "downloadedEver",
     "uploadedEver",
     "corruptEver",
-    "status",
-    "labels",
     "pieceCount",
-    "pieces",
-    "files",
-    "fileStats",
-    "priorities",
-    //"wanted",
-    "peers",
-    "peer",
+    "pieceSize",
+    "peersConnected",
+    "queuePosition",
     "trackers",
-    "trackerStats",
-    "error",
-    "errorString",
 ];
+
+// pieces are for the per file completion
+static FILES_FIELDS: &[&str] = &["files", "fileStats", "priorities", "pieceCount", "pieceSize", "pieces"];
+static PEERS_FIELDS: &[&str] = &["peers"];
+static TRACKERS_FIELDS: &[&str] = &["trackers", "trackerStats"];
+static PIECES_FIELDS: &[&str] = &["pieceCount", "pieceSize", "pieces", "haveValid", "haveUnchecked", "corruptEver"];
+
+impl DetailsTab {
+    pub fn title(self) -> &'static str {
+        match self {
+            DetailsTab::General => "General",
+            DetailsTab::Files => "Files",
+            DetailsTab::Peers => "Peers",
+            DetailsTab::Trackers => "Trackers",
+            DetailsTab::Pieces => "Pieces",
+        }
+    }
+
+    pub fn fields(self) -> Vec<&'static str> {
+        let extra = match self {
+            DetailsTab::General => GENERAL_FIELDS,
+            DetailsTab::Files => FILES_FIELDS,
+            DetailsTab::Peers => PEERS_FIELDS,
+            DetailsTab::Trackers => TRACKERS_FIELDS,
+            DetailsTab::Pieces => PIECES_FIELDS,
+        };
+        DETAILS_BASE_FIELDS.iter().chain(extra).copied().collect()
+    }
+}
+
+// the pane next to the main table shows the general info and the files whatever the tab
+pub fn details_fields(tab: DetailsTab, pane: bool) -> Vec<&'static str> {
+    let mut fields = tab.fields();
+    if pane {
+        for x in DetailsTab::General.fields().into_iter().chain(DetailsTab::Files.fields()) {
+            if !fields.contains(&x) {
+                fields.push(x);
+            }
+        }
+    }
+    fields
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Torrents {
     pub torrents: Vec<TorrentDetails>,
 }
-#[derive(Deserialize, Debug, Clone)]
+// only the fields of the requested tab are filled in, see DetailsTab::fields
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
 pub struct TorrentDetails {
     pub id: u64,
     pub name: String,
     pub eta: i64,
     #[serde(rename = "sizeWhenDone")]
     pub size_when_done: u64,
+    #[serde(rename = "totalSize")]
+    pub total_size: u64,
+    #[serde(rename = "leftUntilDone")]
+    pub left_until_done: u64,
     #[serde(deserialize_with = "status_deserializer")]
     pub status: TorrentStatus,
     #[serde(rename = "downloadDir")]
     pub download_dir: String,
     #[serde(rename = "comment")]
     pub comment: String,
+    pub creator: String,
     #[serde(rename = "hashString")]
     pub hash_string: String,
+    #[serde(rename = "isPrivate")]
+    pub is_private: bool,
     #[serde(rename = "rateDownload")]
     pub rate_download: u64,
     #[serde(rename = "rateUpload")]
@@ -370,19 +538,39 @@ pub struct TorrentDetails {
     pub upload_ratio: f64,
     #[serde(rename = "seedRatioLimit")]
     pub seed_ratio_limit: f64,
+    #[serde(rename = "seedRatioMode")]
+    pub seed_ratio_mode: i64, // 0 global, 1 own limit, 2 unlimited
+    #[serde(rename = "addedDate")]
+    pub added_date: u64,
+    #[serde(rename = "dateCreated")]
+    pub date_created: u64,
+    #[serde(rename = "startDate")]
+    pub start_date: u64,
+    #[serde(rename = "activityDate")]
+    pub activity_date: u64,
     #[serde(rename = "doneDate")]
     pub done_date: u64,
     #[serde(rename = "percentDone")]
     pub percent_complete: f64,
+    #[serde(rename = "haveValid")]
+    pub have_valid: u64,
+    #[serde(rename = "haveUnchecked")]
+    pub have_unchecked: u64,
     #[serde(rename = "downloadedEver")]
     pub downloaded_ever: u64,
     #[serde(rename = "uploadedEver")]
     pub uploaded_ever: u64,
     #[serde(rename = "corruptEver")]
     pub corrupt_ever: u64,
+    #[serde(rename = "peersConnected")]
+    pub peers_connected: u64,
+    #[serde(rename = "queuePosition")]
+    pub queue_position: i64,
     pub labels: Vec<String>,
     #[serde(rename = "pieceCount")]
     pub piece_count: u64,
+    #[serde(rename = "pieceSize")]
+    pub piece_size: u64,
     pub pieces: String, // base64 encoded bitstring
     pub files: Vec<File>,
     #[serde(rename = "fileStats")]
@@ -428,12 +616,100 @@ pub struct TorrentAdd {
     #[serde(rename = "priority-high")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub priority_high: Option<Vec<i64>>,
-    #[serde(rename = "priority-high")]
+    #[serde(rename = "priority-low")]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub priority_low: Option<Vec<i64>>,
+    #[serde(rename = "priority-normal")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority_normal: Option<Vec<i64>>,
+}
+
+// also deserialized from the values edited on the torrent settings dialog
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct TorrentSet {
+    #[serde(default)]
+    pub ids: Vec<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "files-wanted")]
+    pub files_wanted: Option<Vec<i64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "files-unwanted")]
+    pub files_unwanted: Option<Vec<i64>>,
     #[serde(rename = "priority-high")]
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority_high: Option<Vec<i64>>,
+    #[serde(rename = "priority-low")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority_low: Option<Vec<i64>>,
+    #[serde(rename = "priority-normal")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub priority_normal: Option<Vec<i64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "trackerAdd")]
+    pub tracker_add: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "trackerRemove")]
+    pub tracker_remove: Option<Vec<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "trackerReplace")]
+    pub tracker_replace: Option<Vec<Value>>, // id, url pairs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "trackerList")]
+    pub tracker_list: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "honorsSessionLimits")]
+    pub honors_session_limits: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "downloadLimited")]
+    pub download_limited: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "downloadLimit")]
+    pub download_limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "uploadLimited")]
+    pub upload_limited: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "uploadLimit")]
+    pub upload_limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "bandwidthPriority")]
+    pub bandwidth_priority: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "peer-limit")]
+    pub peer_limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "seedRatioMode")]
+    pub seed_ratio_mode: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "seedRatioLimit")]
+    pub seed_ratio_limit: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "seedIdleMode")]
+    pub seed_idle_mode: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "seedIdleLimit")]
+    pub seed_idle_limit: Option<i64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TorrentObjects {
+    pub torrents: Vec<serde_json::Map<String, Value>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct AddedTorrent {
+    pub name: String,
+}
+
+// exactly one of the fields is set, depending on whether the daemon already had the torrent
+#[derive(Deserialize, Debug, Clone)]
+pub struct TorrentAddResult {
+    #[serde(rename = "torrent-added")]
+    pub torrent_added: Option<AddedTorrent>,
+    #[serde(rename = "torrent-duplicate")]
+    pub torrent_duplicate: Option<AddedTorrent>,
 }
 
 static APP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
@@ -461,6 +737,7 @@ impl TransmissionClient {
         TransmissionClient {
             client: b.build().expect("Can't create reqwest http client!"),
             session_id: Mutex::new("".to_string()),
+            rpc_version: AtomicU64::new(0),
             url: url.to_string(),
         }
     }
@@ -474,10 +751,15 @@ impl TransmissionClient {
 
     pub async fn get_session(&self) -> Result<RpcResponse<Session>> {
         self.execute(json!({
-             "method": "session-get",
-             "arguments": {
-                 "fields": ["download-dir", "version"]
-             }
+             "method": "session-get"
+        }))
+        .await
+    }
+
+    pub async fn session_set(&self, settings: &serde_json::Map<String, Value>) -> Result<Value> {
+        self.execute(json!({
+             "method": "session-set",
+             "arguments": &settings
         }))
         .await
     }
@@ -492,19 +774,46 @@ impl TransmissionClient {
         .await
     }
 
-    #[allow(dead_code)]
-    pub async fn get_torrent_details(&self, ids: Vec<i64>) -> Result<RpcResponse<Torrents>> {
+    pub async fn get_torrent_details(&self, ids: Vec<i64>, fields: &[&str]) -> Result<RpcResponse<Torrents>> {
         self.execute(json!({
              "method": "torrent-get",
              "arguments": {
                "ids": &ids,
-               "fields": TORRENT_DETAILS_FIELDS,
+               "fields": &fields,
                "format": "objects"
              }
         }))
         .await
     }
 
+    pub async fn get_torrent_fields(&self, ids: Vec<i64>, fields: &[&str]) -> Result<RpcResponse<TorrentObjects>> {
+        self.execute(json!({
+             "method": "torrent-get",
+             "arguments": {
+               "ids": &ids,
+               "fields": &fields,
+               "format": "objects"
+             }
+        }))
+        .await
+    }
+
+    // all torrents when ids are empty
+    pub async fn get_torrent_trackers(&self, ids: Vec<i64>) -> Result<RpcResponse<TrackersResponse>> {
+        let mut arguments = json!({
+            "fields": ["id", "trackers"],
+            "format": "objects"
+        });
+        if !ids.is_empty() {
+            arguments["ids"] = json!(ids);
+        }
+        self.execute(json!({
+             "method": "torrent-get",
+             "arguments": arguments
+        }))
+        .await
+    }
+
     #[allow(dead_code)]
     pub async fn get_torrents(&self, ids: Vec<i64>, fields: &Vec<&str>) -> Result<Value> {
         self.execute(json!({
@@ -655,7 +964,7 @@ impl TransmissionClient {
         .await
     }
 
-    pub async fn torrent_add(&self, torrent_add: &TorrentAdd) -> Result<Value> {
+    pub async fn torrent_add(&self, torrent_add: &TorrentAdd) -> Result<RpcResponse<TorrentAddResult>> {
         self.execute(json!({
              "method": "torrent-add",
              "arguments": &torrent_add
@@ -663,10 +972,40 @@ impl TransmissionClient {
         .await
     }
 
+    pub async fn torrent_set(&self, torrent_set: &TorrentSet) -> Result<Value> {
+        self.execute(json!({
+             "method": "torrent-set",
+             "arguments": &torrent_set
+        }))
+        .await
+    }
+
+    // renames the last component of `path`, the path is relative to the torrent's download dir
+    pub async fn rename_path(&self, id: i64, path: &str, name: &str) -> Result<Value> {
+        self.execute(json!({
+             "method": "torrent-rename-path",
+             "arguments": {
+                 "ids": [id],
+                 "path": path,
+                 "name": name
+             }
+        }))
+        .await
+    }
+
     pub fn set_session_id(&self, session_id: &str) {
         let mut s = self.session_id.lock().expect("can't get hold of the mutex(");
         *s = session_id.to_string();
     }
+    pub fn set_rpc_version(&self, rpc_version: u64) {
+        self.rpc_version.store(rpc_version, Ordering::Relaxed);
+    }
+
+    // trackerAdd, trackerRemove and trackerReplace are deprecated since transmission 4.0 (rpc 17)
+    pub fn supports_tracker_list(&self) -> bool {
+        self.rpc_version.load(Ordering::Relaxed) >= 17
+    }
+
     pub fn get_session_id(&self) -> String {
         let s = self.session_id.lock().expect("can't get hold of the mutex(");
         s.to_string()
@@ -733,7 +1072,7 @@ impl TransmissionClient {
                  From::from(x)
              })
         } else {
-            Err(Box::new(HttpError::new(&format!("Method failed, result: '{}'", res))))
+            Err(Box::new(RpcError { result: res.to_string() }))
         }
     }
 }
@@ -1,27 +1,42 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashSet;
+
+use crate::columns::{fit_columns, Column};
+use crate::command_processor::Operation;
+use crate::keymap::{KeyAction, Keymap, Mode};
 use crate::config::{Action, Config, Connection, TrafficMonitorOptions, Styles};
-use crate::torrent_stats::TorrentGroupStats;
-use crate::transmission::{TorrentDetails, TorrentInfo};
+use crate::events::{Event, EventLog};
+use crate::settings::{format_value, Setting, SettingKind};
+use crate::transmission::{DetailsTab, Peer, TorrentDetails, TorrentInfo, DETAILS_TABS};
 use tui::{
     backend::Backend,
     layout::{Alignment, Constraint, Direction, Layout, Rect},
     style::Modifier,
     text::{Span, Spans},
     widgets::{
-        Block, BorderType, Borders, Cell, Clear, List, ListItem, ListState, Paragraph, Row, Sparkline, Table, Wrap,
+        Block, BorderType, Borders, Cell, Clear, List, ListItem, Paragraph, Row, Sparkline, Table, Tabs, Wrap,
     },
     Frame,
 };
 
 use crate::utils::{
-    format_download_speed, format_eta, format_percent_done, format_size, format_status, format_time, process_folder,
-    utf8_split, find_file_position,
+    decode_pieces, format_download_speed, format_eta, format_peer_flags, format_piece_map, format_percent_done, format_size,
+    format_status_text, format_time, process_folder, summarize_peers, utf8_split, find_file_position, unix_now,
 };
-use tui_tree_widget::{Tree, TreeItem};
+use tui_tree_widget::{flatten, Tree, TreeItem};
+
+use crate::{describe_torrents, filter_at, is_filter_active, marked_ids, target_ids, settings_values, views_start, sorted_peers, App, SortFunction, SettingsTarget, TrackerEdit, Transition};
 
-use crate::{App, Transition};
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
 
 pub fn ui<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
     let size = frame.size();
+    app.areas.table = None;
+    app.areas.filters = None;
+    app.areas.tree = None;
+    app.areas.chips = vec![];
+    app.areas.details_pane = None;
+    app.areas.menu = vec![];
 
     // FIXME: hide bandwith monitor
     let chunks = Layout::default()
@@ -41,13 +56,33 @@ pub fn ui<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
         )
         .split(size);
 
+    let num_marked = marked_ids(app).len();
+    // the oldest command still waiting for an answer
+    let pending = app.pending.values().next().map_or_else(String::new, |(op, ids)| {
+        let spinner = SPINNER[app.ui_ticks % SPINNER.len()];
+        let more = if app.pending.len() > 1 {
+            format!(" (+{})", app.pending.len() - 1)
+        } else {
+            "".to_string()
+        };
+        match describe_torrents(app, ids) {
+            x if x.is_empty() => format!("{} {}{} | ", spinner, op.name(), more),
+            x => format!("{} {}: {}{} | ", spinner, op.name(), x, more),
+        }
+    });
     let status = Paragraph::new(Spans::from(vec![
+        Span::styled(pending, app.styles.emphasis),
         //Span::styled(format!("W: {}, H: {} ", frame.size().width, frame.size().height),
         //app.styles.text),
         Span::styled(
             format!("🔨 {}", app.config.connections[app.connection_idx].name),
             app.styles.text,
         ),
+        Span::styled(if num_marked > 0 { " | Marked: " } else { "" }, app.styles.text),
+        Span::styled(
+            if num_marked > 0 { num_marked.to_string() } else { "".to_string() },
+            app.styles.emphasis,
+        ),
         //Span::styled(" | Client Mem: ", app.styles.text),
         //Span::styled(format_size(app.memory_usage as i64), app.styles.emphasis),
         Span::styled(" | Free Space: ", app.styles.text),
@@ -74,10 +109,11 @@ pub fn ui<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
     );
 
     if app.transition == Transition::Search || app.transition.is_find() {
-        let search = Paragraph::new(Spans::from(vec![Span::styled(
-            format!("Search: {}▋", app.input),
-            app.styles.emphasis,
-        )]))
+        let mut line = vec![Span::styled(format!("Search: {}▋", app.input), app.styles.emphasis)];
+        if let Some(error) = &app.input_error {
+            line.push(Span::styled(format!("  {}", error), app.styles.error_text));
+        }
+        let search = Paragraph::new(Spans::from(line))
         .alignment(Alignment::Left)
         .block(
             Block::default()
@@ -87,6 +123,19 @@ pub fn ui<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
                 .border_type(BorderType::Plain),
         );
         frame.render_widget(search, chunks[2]);
+    } else if let Some(title) = input_title(&app.transition) {
+        let mut line = vec![Span::styled(format!("{}▋", app.input), app.styles.emphasis)];
+        if let Some(error) = &app.input_error {
+            line.push(Span::styled(format!("  {}", error), app.styles.error_text));
+        }
+        let edit = Paragraph::new(Spans::from(line)).alignment(Alignment::Left).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(app.styles.text)
+                .title(title)
+                .border_type(BorderType::Plain),
+        );
+        frame.render_widget(edit, chunks[2]);
     } else {
         frame.render_widget(status, chunks[2]);
     }
@@ -101,25 +150,40 @@ pub fn ui<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
 
     match app.transition {
         Transition::Help => {
-            let help = help_dialog(&app.styles);
+            let help = help_dialog(&app.keymap, Mode::of(&app.prev_transition), &app.styles);
             let width = if size.width > 120 { 30 } else { 60 };
             let area = centered_rect(width, 90, chunks[1]);
             frame.render_widget(help, area);
         }
-        Transition::Files | Transition::FileAction => {
-            let pets_chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-                .split(chunks[1]);
-            if let Some(details) = &app.details {
-                let details_frame = render_details(details, &app.styles);
-
-                let area = centered_rect(90, 90, pets_chunks[0]);
-                frame.render_widget(details_frame, area);
-
-                let block = draw_tree(app.tree_items.clone(), &app.styles);
-                frame.render_stateful_widget(block, pets_chunks[1], &mut app.tree_state);
-            }
+        Transition::Settings | Transition::EditSetting if app.settings_target == SettingsTarget::Session => {
+            let title = app
+                .session
+                .as_ref()
+                .map_or_else(|| "Settings".to_string(), |s| format!("Settings: Transmission {}", s.version));
+            let title = settings_title(app, title);
+            let settings = render_settings(
+                app.settings_target.settings(),
+                settings_values(app),
+                &app.settings_changed,
+                title,
+                &app.styles,
+            );
+            frame.render_stateful_widget(settings, chunks[1], &mut app.settings_state);
+        }
+        Transition::Log => {
+            draw_event_log(frame, app, chunks[1]);
+        }
+        Transition::General
+        | Transition::Files
+        | Transition::FileAction
+        | Transition::RenameFile
+        | Transition::FileFilter
+        | Transition::Peers
+        | Transition::Trackers
+        | Transition::TrackerInput(_)
+        | Transition::Pieces => {
+            let tab = app.transition.details_tab().unwrap_or_default();
+            draw_details_screen(frame, app, tab, chunks[1]);
         }
         _ => {
             let pets_chunks = Layout::default()
@@ -136,51 +200,87 @@ pub fn ui<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
                     .as_ref(),
                 )
                 .split(chunks[1]);
-            let filters = render_filters(
-                &app.groups,
-                &app.transition,
-                &app.folder_mapping,
-                app.num_active,
-                &app.config.connections[app.connection_idx],
-                &app.styles
-            );
-            let main_table = render_main_table(&app.left_filter_state, &app.groups, &app.filtered_torrents, &app.styles);
-
-            if size.width > 120 {
-                frame.render_stateful_widget(filters, pets_chunks[0], &mut app.left_filter_state);
-                frame.render_stateful_widget(main_table, pets_chunks[1], &mut app.main_table_state);
+            let filters = render_filters(app);
+            let (table_area, filters_area) = if size.width > 120 {
+                (pets_chunks[1], Some(pets_chunks[0]))
+            } else if app.transition == Transition::Filter {
+                (chunks[1], Some(pets_chunks[0]))
             } else {
-                frame.render_stateful_widget(main_table, chunks[1], &mut app.main_table_state);
-                if app.transition == Transition::Filter {
-                    frame.render_widget(Clear, pets_chunks[0]);
-                    frame.render_stateful_widget(filters, pets_chunks[0], &mut app.left_filter_state);
-                }
+                (chunks[1], None)
+            };
+            // wide terminals get the selected torrent next to the table
+            let table_area = if app.config.details_pane > 0 && size.width >= app.config.details_pane {
+                let parts = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Min(0), Constraint::Length(size.width / 3)].as_ref())
+                    .split(table_area);
+                app.areas.details_pane = Some(parts[1]);
+                draw_details_pane(frame, app, parts[1]);
+                parts[0]
+            } else {
+                table_area
+            };
+            let table_area = if app.filters.is_empty() {
+                table_area
+            } else {
+                let parts = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+                    .split(table_area);
+                let chips = render_chips(app, parts[0]);
+                frame.render_widget(chips, parts[0]);
+                parts[1]
+            };
+            let columns = fit_columns(&app.config.columns, table_area.width.saturating_sub(2));
+            let widths: Vec<Constraint> = columns.iter().map(|x| x.constraint()).collect();
+            let marked = marked_ids(app);
+            let main_table = render_main_table(
+                &app.filtered_torrents,
+                &marked,
+                &columns,
+                &widths,
+                &app.config.connections[app.connection_idx].download_dir,
+                format!("Darlings, sorted {}", app.sort_func().name()),
+                &app.styles,
+            );
+            track_main_table(app, table_area, &columns, &widths);
+            frame.render_stateful_widget(main_table, table_area, &mut app.main_table_state);
+            if let Some(area) = filters_area {
+                app.areas.filters_offset = scroll_offset(
+                    app.areas.filters_offset,
+                    app.left_filter_state.selected(),
+                    filter_list_len(app),
+                    area.height.saturating_sub(2) as usize,
+                );
+                app.areas.filters = Some(area);
+                frame.render_widget(Clear, area);
+                frame.render_stateful_widget(filters, area, &mut app.left_filter_state);
             }
         }
     }
-    if let Some((msg, details)) = &app.err {
-        let area = centered_rect(36, 40, size);
-        let block = error_dialog(msg, details, &app.styles);
-        frame.render_widget(Clear, area);
-        frame.render_widget(block, area);
+    if app.transition != Transition::Log {
+        draw_toasts(frame, &app.events, chunks[2].y, &app.styles);
     }
     match app.transition {
         Transition::Action => {
             let block = Block::default().title("Actions").borders(Borders::ALL);
             let area_width = if size.width > 120 { 16 } else { 38 };
-            let area = centered_rect(area_width, 42, size);
+            let area = centered_rect(area_width, 50, size);
             let vert_layout = Layout::default()
                 .direction(Direction::Vertical)
-                .constraints([Constraint::Length(3), Constraint::Length(15)].as_ref())
+                .constraints([Constraint::Length(3), Constraint::Min(1)].as_ref())
                 .split(block.inner(area));
-            let list = action_menu(&app.config.actions, &app.styles);
+            let list = action_menu(&app.config.actions, &app.keymap, &app.styles);
             frame.render_widget(Clear, area);
             frame.render_widget(block, area);
 
+            let targets = marked_targets(app);
             let title = app.selected.as_ref().map_or_else(
                 || "".to_string(),
                 |x| {
-                    if x.name.len() > 25 {
+                    if let Some(targets) = &targets {
+                        format!("\n {}", targets)
+                    } else if x.name.len() > 25 {
                         "\n ".to_owned() + &x.name.chars().take(25).collect::<String>() + "…"
                     } else {
                         "\n ".to_owned() + &x.name
@@ -193,6 +293,8 @@ pub fn ui<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
 
             frame.render_widget(status, vert_layout[0]);
             frame.render_widget(list, vert_layout[1]);
+            let keys = menu_keys(&app.config.actions, &app.keymap, Mode::Action);
+            add_menu_rows(app, vert_layout[1], keys);
         }
         Transition::FileAction => {
             let block = Block::default().title("File Actions").borders(Borders::ALL);
@@ -203,7 +305,7 @@ pub fn ui<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
                 .constraints([Constraint::Length(3), Constraint::Length(15)].as_ref())
                 .split(block.inner(area));
             //if let Some(details) = &app.details {
-            let details = file_action_menu(&app.config.file_actions, &app.styles);
+            let details = file_action_menu(&app.config.file_actions, &app.keymap, &app.styles);
             frame.render_widget(Clear, area);
             frame.render_widget(block, area);
 
@@ -231,6 +333,8 @@ pub fn ui<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
 
             frame.render_widget(status, vert_layout[0]);
             frame.render_widget(details, vert_layout[1]);
+            let keys = menu_keys(&app.config.file_actions, &app.keymap, Mode::FileAction);
+            add_menu_rows(app, vert_layout[1], keys);
             //}
         }
 
@@ -241,9 +345,32 @@ pub fn ui<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
                 .and_then(|x| app.filtered_torrents.get(x))
             {
                 let area = centered_rect(46, 15, size);
-                let block = delete_confirmation_dialog(with_data, &x.name, &app.styles);
+                let name = marked_targets(app).unwrap_or_else(|| format!("'{}'", x.name));
+                let block = Block::default().title("Confirm").borders(Borders::ALL);
+                let vert_layout = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+                    .split(block.inner(area));
+                let message = delete_confirmation_dialog(with_data, name, &app.styles);
                 frame.render_widget(Clear, area);
                 frame.render_widget(block, area);
+                frame.render_widget(message, vert_layout[0]);
+
+                let key_style = app.styles.emphasis.add_modifier(Modifier::UNDERLINED);
+                let y = vert_layout[1].y;
+                let buttons = [('y', " Yes "), ('n', " No ")];
+                let mut x = vert_layout[1].x + 1;
+                for (key, label) in buttons {
+                    let width = 3 + label.len() as u16;
+                    let button = Rect::new(x, y, width, 1);
+                    let text = Paragraph::new(Spans::from(vec![
+                        Span::styled(format!("[{}]", key), key_style),
+                        Span::styled(label, app.styles.text),
+                    ]));
+                    frame.render_widget(text, button);
+                    app.areas.menu.push((button, KeyEvent::new(KeyCode::Char(key), KeyModifiers::NONE)));
+                    x += width + 2;
+                }
             }
         }
         Transition::Move => {
@@ -252,172 +379,402 @@ pub fn ui<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
                 .selected()
                 .and_then(|x| app.filtered_torrents.get(x))
             {
+                let name = marked_targets(app).unwrap_or_else(|| x.name.clone());
                 move_dialog(
                     frame,
-                    &x.name,
+                    &name,
                     &app.folder_mapping,
                     &app.config.connections[app.connection_idx],
                     &app.styles
                 );
             }
         }
+        Transition::AddTorrent | Transition::AddTorrentDir => {
+            add_torrent_dialog(
+                frame,
+                &app.transition,
+                &app.input,
+                app.add_paused,
+                &app.folder_mapping,
+                &app.config.connections[app.connection_idx],
+                &app.styles,
+            );
+        }
+        Transition::Settings | Transition::EditSetting => {
+            let title = app.selected.as_ref().map_or_else(
+                || "Torrent Settings".to_string(),
+                |x| format!("Torrent Settings: {}", x.name.chars().take(30).collect::<String>()),
+            );
+            let title = settings_title(app, title);
+            let area_width = if size.width > 120 { 30 } else { 60 };
+            let area = centered_rect(area_width, 50, size);
+            let vert_layout = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(1), Constraint::Length(1)].as_ref())
+                .split(area);
+            let settings = render_settings(
+                app.settings_target.settings(),
+                settings_values(app),
+                &app.settings_changed,
+                title,
+                &app.styles,
+            );
+            let key_style = app.styles.emphasis.add_modifier(Modifier::UNDERLINED);
+            let hint = Paragraph::new(Spans::from(vec![
+                Span::styled(" w", key_style),
+                Span::styled(" save  ", app.styles.text),
+                Span::styled("Esc", key_style),
+                Span::styled(
+                    if app.settings_changed.is_empty() { " cancel" } else { " discard changes" },
+                    app.styles.text,
+                ),
+            ]));
+            frame.render_widget(Clear, area);
+            frame.render_stateful_widget(settings, vert_layout[0], &mut app.settings_state);
+            frame.render_widget(hint, vert_layout[1]);
+        }
         Transition::ChooseSortFunc => {
-            let area = centered_rect(26, 35, size);
-            let block = choose_sort_dialog(&app.styles);
+            let area = centered_rect(36, 60, size);
+            let block = choose_sort_dialog(app.sort_func(), app.sort_then, &app.keymap, &app.styles);
             frame.render_widget(Clear, area);
             frame.render_widget(block, area);
+            // the entries follow the border and five lines of header
+            let keys = app.keymap.menu_events(Mode::Sort);
+            add_menu_rows(app, Rect::new(area.x, area.y + 6, area.width, area.height.saturating_sub(7)), keys);
         }
         Transition::Connection => {
             let area = centered_rect(26, 35, size);
             let block = choose_connection(&app.config, &app.styles);
             frame.render_widget(Clear, area);
             frame.render_widget(block, area);
+            // the connections follow the border and three lines of header
+            let keys = (0..app.config.connections.len())
+                .map(|i| char::from_digit(i as u32 + 1, 10).map(|c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)))
+                .collect();
+            add_menu_rows(app, Rect::new(area.x, area.y + 4, area.width, area.height.saturating_sub(5)), keys);
         }
         _ => {}
     }
 }
 
+// tui keeps scroll offsets private, so we follow its logic to map mouse clicks onto rows
+fn scroll_offset(offset: usize, selected: Option<usize>, len: usize, height: usize) -> usize {
+    if len == 0 || height == 0 {
+        return 0;
+    }
+    let offset = offset.min(len - 1);
+    let selected = selected.unwrap_or(0).min(len - 1);
+    if selected < offset {
+        selected
+    } else if selected >= offset + height {
+        selected + 1 - height
+    } else {
+        offset
+    }
+}
+
+// remembers where the rows and header columns of the main table end up on the screen
+fn track_main_table(app: &mut App, area: Rect, columns: &[Column], widths: &[Constraint]) {
+    let inner = Rect {
+        x: area.x + 1,
+        y: area.y + 1,
+        width: area.width.saturating_sub(2),
+        height: area.height.saturating_sub(2),
+    };
+    app.areas.table_offset = scroll_offset(
+        app.areas.table_offset,
+        app.main_table_state.selected(),
+        app.filtered_torrents.len(),
+        inner.height.saturating_sub(1) as usize,
+    );
+    let mut constraints = vec![];
+    for width in widths {
+        constraints.push(*width);
+        constraints.push(Constraint::Length(1));
+    }
+    constraints.pop();
+    app.areas.table_columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(constraints)
+        .split(Rect { height: 1, ..inner })
+        .iter()
+        .step_by(2)
+        .zip(columns)
+        .map(|(x, column)| (*column, x.x, x.width))
+        .collect();
+    app.areas.table = Some(inner);
+}
+
+// active filters above the main table, clicking one removes it
+fn render_chips<'a>(app: &mut App, area: Rect) -> Paragraph<'a> {
+    let mut spans = vec![Span::raw(" ")];
+    let mut x = area.x + 1;
+    app.areas.chips = vec![];
+    for filter in &app.filters {
+        let chip = Span::styled(format!(" {} ✕ ", filter.chip(app)), app.styles.highlight);
+        let width = chip.width() as u16;
+        app.areas.chips.push((filter.facet(), Rect { x, y: area.y, width, height: 1 }));
+        x += width + 1;
+        spans.push(chip);
+        spans.push(Span::raw(" "));
+    }
+    Paragraph::new(Spans::from(spans))
+}
+
+fn filter_list_len(app: &App) -> usize {
+    if app.groups.views.is_empty() {
+        views_start(app) - 1
+    } else {
+        views_start(app) + app.groups.views.len()
+    }
+}
+
 fn render_main_table<'a>(
-    _filter_state: &ListState,
-    _groups: &TorrentGroupStats,
     torrents: &[TorrentInfo],
+    marked: &HashSet<i64>,
+    columns: &[Column],
+    widths: &'a [Constraint],
+    base_dir: &str,
+    title: String,
     styles: &Styles
 ) -> Table<'a> {
     let rows: Vec<_> = torrents
         .iter()
         .map(|x| {
-            Row::new(vec![
-                Cell::from(Span::raw(format_status(&x.status, x.error))),
-                Cell::from(Span::raw(x.name.clone())),
-                Cell::from(Span::raw(format_percent_done(x.percent_done))),
-                Cell::from(Span::raw(format_eta(x.eta))),
-                Cell::from(Span::raw(format_size(x.size_when_done))),
-                Cell::from(Span::raw(format_download_speed(x.rate_upload, true))),
-                Cell::from(Span::raw(format_download_speed(x.rate_download, true))),
-                Cell::from(Span::raw(format_size(x.uploaded_ever))),
-            ])
+            let style = if marked.contains(&x.id) {
+                styles.emphasis.add_modifier(Modifier::BOLD)
+            } else {
+                styles.text
+            };
+            Row::new(columns.iter().map(|c| Cell::from(Span::raw(c.cell(x, base_dir)))).collect::<Vec<_>>())
+                .style(style)
         })
         .collect();
     let pet_detail = Table::new(rows)
         .highlight_style(
             styles.highlight
         )
-        .header(Row::new(vec![
-            Cell::from(Span::styled(" ", styles.emphasis)),
-            Cell::from(Span::styled("Name", styles.emphasis)),
-            Cell::from(Span::styled("Done", styles.emphasis)),
-            Cell::from(Span::styled("Eta", styles.emphasis)),
-            Cell::from(Span::styled("Size", styles.emphasis)),
-            Cell::from(Span::styled("Up", styles.emphasis)),
-            Cell::from(Span::styled("Down", styles.emphasis)),
-            Cell::from(Span::styled("Uploaded", styles.emphasis)),
-        ]))
+        .header(Row::new(
+            columns
+                .iter()
+                .map(|c| Cell::from(Span::styled(c.title(), styles.emphasis)))
+                .collect::<Vec<_>>(),
+        ))
         .block(
             Block::default()
                 .borders(Borders::ALL)
                 .style(styles.text)
-                .title("Darlings")
+                .title(title)
                 .border_type(BorderType::Plain),
         )
-        .widths(&[
-            Constraint::Min(3),
-            Constraint::Percentage(40),
-            Constraint::Percentage(5),
-            Constraint::Percentage(10),
-            Constraint::Percentage(10),
-            Constraint::Percentage(10),
-            Constraint::Percentage(10),
-            Constraint::Percentage(10),
-            Constraint::Percentage(10),
-        ]);
+        .widths(widths);
 
     pet_detail
 }
 
-fn render_filters<'a>(
-    groups: &TorrentGroupStats,
-    transition: &Transition,
-    mapping: &[(String, char, usize)],
-    num_active: usize,
-    connection: &Connection,
+// changed values are highlighted until they're saved
+// torrent settings wait for `w`, session settings are sent right away and wait for the daemon
+fn settings_title(app: &App, title: String) -> String {
+    let unsaved = match app.settings_target {
+        SettingsTarget::Session => app.pending.values().any(|x| x.0 == Operation::SessionSettings),
+        SettingsTarget::Torrents(_) => !app.settings_changed.is_empty(),
+    };
+    if unsaved {
+        title + " [unsaved]"
+    } else {
+        title
+    }
+}
+
+fn render_settings<'a>(
+    settings: &[Setting],
+    values: Option<&serde_json::Map<String, serde_json::Value>>,
+    changed: &serde_json::Map<String, serde_json::Value>,
+    title: String,
     styles: &Styles,
-) -> List<'a> {
+) -> Table<'a> {
+    let rows: Vec<_> = settings
+        .iter()
+        .map(|x| {
+            if x.kind == SettingKind::Section {
+                Row::new(vec![Cell::from(Span::styled(x.label, styles.details_emphasis))])
+            } else {
+                let value = values.and_then(|s| s.get(x.key));
+                let value_style = if changed.contains_key(x.key) {
+                    styles.emphasis
+                } else {
+                    styles.blend_in
+                };
+                Row::new(vec![
+                    Cell::from(Span::styled(format!("  {}", x.label), styles.text)),
+                    Cell::from(Span::styled(format_value(x, value), value_style)),
+                ])
+            }
+        })
+        .collect();
+    Table::new(rows)
+        .highlight_style(styles.highlight)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(styles.text)
+                .title(title)
+                .border_type(BorderType::Plain),
+        )
+        .widths(&[Constraint::Length(30), Constraint::Min(10)])
+}
+
+fn render_filters<'a>(app: &App) -> List<'a> {
+    let groups = &app.groups;
+    let transition = &app.transition;
+    let mapping = &app.folder_mapping;
+    let label_mapping = &app.label_mapping;
+    let num_active = app.num_active;
+    let styles = &app.styles;
     let filters = Block::default()
         .borders(Borders::ALL)
         .style(styles.text)
         .title("Filters")
         .border_type(BorderType::Plain);
+    // in the order of builtin_filter
     let filter_items = vec![
-        ("Recent".to_string(), 'R', 0),
-        (format!("Active: {}", num_active), 'A', 0),
-        (format!("Paused: {}", groups.num_stopped), 'P', 0),
-        (format!("Checking queue: {}", groups.num_queue_checking), 'G', 7),
-        (format!("Checking: {}", groups.num_checking), 'C', 0),
-        (format!("Download queue: {}", groups.num_queue_down), 'Q', 9),
-        (format!("Downloading: {}", groups.num_downloading), 'D', 0),
-        (format!("Seeding queue: {}", groups.num_queue_up), 'U', 9),
-        (format!("Seeding: {}", groups.num_seeding), 'S', 0),
-        (format!("Error: {}", groups.num_error), 'E', 0),
-        (format!("All: {}", groups.num_total), 'L', 1),
+        "Recent".to_string(),
+        format!("Active: {}", num_active),
+        format!("Paused: {}", groups.num_stopped),
+        format!("Checking queue: {}", groups.num_queue_checking),
+        format!("Checking: {}", groups.num_checking),
+        format!("Download queue: {}", groups.num_queue_down),
+        format!("Downloading: {}", groups.num_downloading),
+        format!("Seeding queue: {}", groups.num_queue_up),
+        format!("Seeding: {}", groups.num_seeding),
+        format!("Error: {}", groups.num_error),
+        format!("Stalled: {}", groups.num_stalled),
+        format!("Finished in {}d: {}", app.config.finished_days, groups.num_finished),
+        format!("Incomplete: {}", groups.num_incomplete),
+        format!("Private: {}", groups.num_private),
+        format!("Awaiting metadata: {}", groups.num_awaiting_metadata),
+        format!("All: {}", groups.num_total),
     ];
-    let mut folders: Vec<_> = groups.folders.iter().collect();
-    folders.sort();
-    let mut folder_items: Vec<_> = folders
+    // the folder tree, ▸ marks closed folders with subfolders
+    let mut folder_items: Vec<_> = app
+        .folder_rows()
+        .iter()
+        .map(|x| {
+            let arrow = match (x.has_children, x.expanded) {
+                (false, _) => "  ",
+                (true, false) => "▸ ",
+                (true, true) => "▾ ",
+            };
+            let indent = format!(" {}{}", "  ".repeat(x.depth), arrow);
+            let totals = format!(": {}, {}", x.count, format_size(x.size));
+            match mapping.iter().find(|y| y.0 == x.path) {
+                Some((_, c, i)) if transition == &Transition::Filter => {
+                    let (first, second) = utf8_split(&x.name, *i);
+                    let second: String = second.chars().skip(1).collect();
+                    ListItem::new(Spans::from(vec![
+                        Span::styled(indent, styles.text),
+                        Span::styled(first, styles.text),
+                        Span::styled(c.to_string(), styles.emphasis.add_modifier(Modifier::UNDERLINED)),
+                        Span::styled(second, styles.text),
+                        Span::styled(totals, styles.text),
+                    ]))
+                }
+                _ => ListItem::new(Spans::from(vec![
+                    Span::styled(indent, styles.text),
+                    Span::styled(x.name.clone(), styles.text),
+                    Span::styled(totals, styles.text),
+                ])),
+            }
+        })
+        .collect();
+
+    let mut labels: Vec<_> = groups.labels.iter().collect();
+    labels.sort();
+    let mut label_items: Vec<_> = labels
         .iter()
-        .map(|f| {
-            let name = process_folder(f.0, &connection.download_dir);
-            if transition == &Transition::Filter {
-                let (_, c, i) = mapping.iter().find(|y| &y.0 == f.0).expect("exist");
-                let (first, second) = utf8_split(&name, *i);
+        .map(|l| match label_mapping.iter().find(|y| &y.0 == l.0) {
+            Some((_, c, i)) if transition == &Transition::Filter => {
+                let (first, second) = utf8_split(l.0, *i);
                 let second: String = second.chars().skip(1).collect();
                 ListItem::new(Spans::from(vec![
                     Span::raw(" "),
                     Span::styled(first, styles.text),
-                    Span::styled(
-                        c.to_string(),
-                        styles.emphasis
-                            .add_modifier(Modifier::UNDERLINED)
-                    ),
-                    Span::styled(format!("{}: {}", second, f.1), styles.text),
+                    Span::styled(c.to_string(), styles.emphasis.add_modifier(Modifier::UNDERLINED)),
+                    Span::styled(format!("{}: {}", second, l.1), styles.text),
                 ]))
-            } else {
-                ListItem::new(Spans::from(vec![Span::styled(
-                    format!(" {}: {}", name, f.1),
-                    styles.text,
-                )]))
             }
+            _ => ListItem::new(Spans::from(vec![Span::styled(format!(" {}: {}", l.0, l.1), styles.text)])),
         })
         .collect();
 
-    let mut items: Vec<_> = filter_items
+    let mut view_items: Vec<_> = app
+        .config
+        .views
         .iter()
-        .map(|x| {
-            if transition == &Transition::Filter {
-                let (first, second) = utf8_split(&x.0, x.2);
+        .zip(&groups.views)
+        .map(|(v, n)| match app.view_mapping.iter().find(|y| y.0 == v.name) {
+            Some((_, c, i)) if transition == &Transition::Filter => {
+                let (first, second) = utf8_split(&v.name, *i);
                 let second: String = second.chars().skip(1).collect();
-
                 ListItem::new(Spans::from(vec![
                     Span::raw(" "),
                     Span::styled(first, styles.text),
-                    Span::styled(
-                        x.1.to_string(),
-                        styles.emphasis
-                            .add_modifier(Modifier::UNDERLINED)
-                    ),
-                    Span::styled(second, styles.text),
-                ]))
-            } else {
-                ListItem::new(Spans::from(vec![
-                    Span::raw(" "),
-                    Span::styled(x.0.clone(), styles.text),
+                    Span::styled(c.to_string(), styles.emphasis.add_modifier(Modifier::UNDERLINED)),
+                    Span::styled(format!("{}: {}", second, n), styles.text),
                 ]))
             }
+            _ => ListItem::new(Spans::from(vec![Span::styled(format!(" {}: {}", v.name, n), styles.text)])),
+        })
+        .collect();
+
+    let mut items: Vec<_> = filter_items
+        .iter()
+        .enumerate()
+        .map(|(i, x)| {
+            let key = app.keymap.key_char(Mode::Filter, KeyAction::BuiltinFilter(i));
+            match key {
+                Some(c) if transition == &Transition::Filter => {
+                    // the key goes in place of the same letter in the name, or after it
+                    let pos = x.chars().position(|y| y.to_lowercase().eq(c.to_lowercase()));
+                    let (first, second) = match pos {
+                        Some(pos) => {
+                            let (first, second) = utf8_split(x, pos);
+                            (first, second.chars().skip(1).collect())
+                        }
+                        None => (x.clone() + " ", String::new()),
+                    };
+                    ListItem::new(Spans::from(vec![
+                        Span::raw(" "),
+                        Span::styled(first, styles.text),
+                        Span::styled(c.to_string(), styles.emphasis.add_modifier(Modifier::UNDERLINED)),
+                        Span::styled(second, styles.text),
+                    ]))
+                }
+                _ => ListItem::new(Spans::from(vec![Span::raw(" "), Span::styled(x.clone(), styles.text)])),
+            }
         })
         .collect();
 
     items.push(ListItem::new("────────────────────────".to_string()));
     items.append(&mut folder_items);
+    if !label_items.is_empty() {
+        items.push(ListItem::new("────────────────────────".to_string()));
+        items.append(&mut label_items);
+    }
+    if !view_items.is_empty() {
+        items.push(ListItem::new("────────────────────────".to_string()));
+        items.append(&mut view_items);
+    }
 
+    // every active filter is highlighted, there can be one per facet
+    let items: Vec<_> = items
+        .into_iter()
+        .enumerate()
+        .map(|(i, x)| match filter_at(app, i) {
+            Some(filter) if is_filter_active(app, &filter) => x.style(styles.highlight),
+            _ => x,
+        })
+        .collect();
     let list = List::new(items).block(filters).highlight_style(
         styles.highlight
             .add_modifier(Modifier::BOLD),
@@ -426,28 +783,14 @@ fn render_filters<'a>(
     list
 }
 
-fn action_menu<'a>(actions: &'a [Action], styles: &Styles) -> List<'a> {
-    let mut xs: Vec<(&str, &str)> = actions
+fn action_menu<'a>(actions: &'a [Action], keymap: &Keymap, styles: &Styles) -> List<'a> {
+    let mut xs: Vec<(String, &str)> = actions
         .iter()
-        .map(|x| (x.shortcut.as_str(), x.description.as_str()))
+        .map(|x| (x.shortcut.clone(), x.description.as_str()))
         .collect();
 
-    let mut ys = vec![
-        ("", "───"),
-        ("s", "Start"),
-        ("S", "Start Now"),
-        ("p", "Pause"),
-        ("v", "Verify"),
-        ("m", "Move"),
-        ("x", "Remove"),
-        ("X", "Remove with data"),
-        ("", "───"),
-        ("k", "Queue Up"),
-        ("j", "Queue Down"),
-        ("K", "Queue Top"),
-        ("J", "Queue Bottom"),
-    ];
-    xs.append(&mut ys);
+    xs.push(("".to_string(), "───"));
+    xs.extend(keymap.menu(Mode::Action));
     let items: Vec<_> = xs
         .iter()
         .map(|x| {
@@ -463,7 +806,7 @@ fn action_menu<'a>(actions: &'a [Action], styles: &Styles) -> List<'a> {
                     Span::raw(" ")
                 },
                 Span::styled(
-                    x.0,
+                    x.0.clone(),
                     styles.emphasis
                         .add_modifier(Modifier::UNDERLINED)
                 ),
@@ -474,22 +817,35 @@ fn action_menu<'a>(actions: &'a [Action], styles: &Styles) -> List<'a> {
     List::new(items)
 }
 
-fn file_action_menu<'a>(actions: &'a [Action], styles: &'a Styles) -> List<'a> {
-    let xs: Vec<(&str, &str)> = actions
+// what clicking each line of action_menu or file_action_menu sends: the shortcut, nothing for the separator,
+// then the first key of each binding
+fn menu_keys(actions: &[Action], keymap: &Keymap, mode: Mode) -> Vec<Option<KeyEvent>> {
+    let mut keys: Vec<Option<KeyEvent>> = actions
         .iter()
-        .map(|x| (x.shortcut.as_str(), x.description.as_str()))
+        .map(|x| x.shortcut.chars().next().map(|c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)))
         .collect();
+    keys.push(None);
+    keys.extend(keymap.menu_events(mode));
+    keys
+}
 
-    /*let mut ys = vec![
-        ("", "───"),
-        ("+", "Download this file"),
-        ("-", "Skip"),
-        ("r", "Rename"),
-        ("l", "Low Priority"),
-        ("m", "Medium Priority"),
-        ("h", "High Priority"),
-    ];
-    xs.append(&mut ys);*/
+// one clickable row per menu line, as many as the area shows
+fn add_menu_rows(app: &mut App, area: Rect, keys: Vec<Option<KeyEvent>>) {
+    for (i, key) in keys.into_iter().enumerate().take(area.height as usize) {
+        if let Some(key) = key {
+            app.areas.menu.push((Rect::new(area.x, area.y + i as u16, area.width, 1), key));
+        }
+    }
+}
+
+fn file_action_menu<'a>(actions: &'a [Action], keymap: &Keymap, styles: &'a Styles) -> List<'a> {
+    let mut xs: Vec<(String, &str)> = actions
+        .iter()
+        .map(|x| (x.shortcut.clone(), x.description.as_str()))
+        .collect();
+
+    xs.push(("".to_string(), "───"));
+    xs.extend(keymap.menu(Mode::FileAction));
     let items: Vec<_> = xs
         .iter()
         .map(|x| {
@@ -505,7 +861,7 @@ fn file_action_menu<'a>(actions: &'a [Action], styles: &'a Styles) -> List<'a> {
                     Span::raw(" ")
                 },
                 Span::styled(
-                    x.0,
+                    x.0.clone(),
                     styles.emphasis
                         .add_modifier(Modifier::UNDERLINED)
                 ),
@@ -516,45 +872,115 @@ fn file_action_menu<'a>(actions: &'a [Action], styles: &'a Styles) -> List<'a> {
     List::new(items)
 }
 
-fn error_dialog<'a>(msg: &'a str, details: &'a str, styles: &Styles) -> Paragraph<'a> {
-    let mut lines = vec![
-        Spans::from(vec![Span::raw("")]),
-        Spans::from(vec![Span::styled("Bloody hell!", styles.error_text)]),
-        Spans::from(vec![Span::raw("")]),
-        Spans::from(vec![Span::styled(msg, styles.error_text)]),
-    ];
-    for l in details.split('\n') {
+// recent events stacked in the bottom right corner, above the status bar
+fn draw_toasts<B: Backend>(frame: &mut Frame<B>, events: &EventLog, bottom: u16, styles: &Styles) {
+    let size = frame.size();
+    let width = size.width.saturating_sub(4).min(50);
+    let mut bottom = bottom;
+    for event in events.toasts(unix_now()) {
+        let lines = 1 + event.details.lines().count().min(2) as u16;
+        let height = lines + 2;
+        if width < 10 || bottom < height {
+            break;
+        }
+        bottom -= height;
+        let area = Rect::new(size.width.saturating_sub(width + 2), bottom, width, height);
+        frame.render_widget(Clear, area);
+        frame.render_widget(toast(event, styles), area);
+    }
+}
+
+fn toast<'a>(event: &'a Event, styles: &Styles) -> Paragraph<'a> {
+    let style = if event.kind.is_error() { styles.error_text } else { styles.emphasis };
+    let mut lines = vec![Spans::from(vec![Span::styled(event.title(), style)])];
+    for l in event.details.lines().take(2) {
         lines.push(Spans::from(vec![Span::styled(l, styles.blend_in)]));
     }
-    let message = Paragraph::new(lines).wrap(Wrap { trim: false }).block(
+    Paragraph::new(lines).block(
         Block::default()
-            .title("Aarrgh!")
+            .title(format!("{}: L event log", event.kind.name()))
             .borders(Borders::ALL)
-            .border_style(styles.error_text),
-    );
-    message
+            .border_style(style),
+    )
+}
+
+// the log on top, details of the selected event below
+fn draw_event_log<B: Backend>(frame: &mut Frame<B>, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(8)].as_ref())
+        .split(area);
+    let rows: Vec<Row> = app
+        .events
+        .iter()
+        .map(|x| {
+            let style = if x.kind.is_error() { app.styles.error_text } else { app.styles.text };
+            Row::new(vec![
+                Cell::from(format_time(x.time as u64)),
+                Cell::from(x.kind.name()),
+                Cell::from(x.title()),
+                Cell::from(x.details.replace('\n', " ")),
+            ])
+            .style(style)
+        })
+        .collect();
+    let table = Table::new(rows)
+        .header(Row::new(vec!["Time", "Kind", "Event", "Details"]).style(app.styles.emphasis))
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Event log: {} events, newest first", app.events.len())),
+        )
+        .highlight_style(app.styles.highlight)
+        .widths(&[
+            Constraint::Length(19),
+            Constraint::Length(13),
+            Constraint::Percentage(30),
+            Constraint::Percentage(70),
+        ])
+        .column_spacing(1);
+    frame.render_stateful_widget(table, chunks[0], &mut app.log_state);
+
+    let selected = app.log_state.selected().and_then(|i| app.events.iter().nth(i));
+    let lines: Vec<Spans> = selected.map_or_else(Vec::new, |x| {
+        let mut lines = vec![Spans::from(vec![Span::styled(x.title(), app.styles.emphasis)])];
+        if x.count > 1 {
+            lines.push(Spans::from(vec![Span::styled(
+                format!("{} times, last at {}", x.count, format_time(x.last as u64)),
+                app.styles.blend_in,
+            )]));
+        }
+        for l in x.details.lines() {
+            lines.push(Spans::from(vec![Span::styled(l, app.styles.text)]));
+        }
+        lines
+    });
+    let details = Paragraph::new(lines)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title("Details"));
+    frame.render_widget(details, chunks[1]);
 }
 
-fn choose_sort_dialog(styles: &Styles) -> Paragraph {
+fn choose_sort_dialog<'a>(sort_func: SortFunction, then: bool, keymap: &Keymap, styles: &Styles) -> Paragraph<'a> {
     let key_style = styles.emphasis
         .add_modifier(Modifier::UNDERLINED);
-    let lines = vec![
+    let mut lines = vec![
         Spans::from(vec![Span::raw("")]),
-        Spans::from(vec![Span::styled(" Choose sort function:", styles.text)]),
+        Spans::from(vec![Span::styled(format!(" Sorted {}", sort_func.name()), styles.text)]),
+        Spans::from(vec![Span::raw("")]),
+        Spans::from(vec![Span::styled(
+            if then { " Choose secondary key:" } else { " Choose sort key:" },
+            styles.text,
+        )]),
         Spans::from(vec![Span::raw("")]),
-        Spans::from(vec![
-            Span::raw(" By "),
-            Span::styled("d", key_style),
-            Span::raw("ate added (default)"),
-        ]),
-        Spans::from(vec![
-            Span::raw(" By "),
-            Span::styled("u", key_style),
-            Span::raw("ploaded total"),
-        ]),
-        Spans::from(vec![Span::raw(" By "), Span::styled("s", key_style), Span::raw("ize")]),
-        Spans::from(vec![Span::raw(" By "), Span::styled("r", key_style), Span::raw("atio")]),
     ];
+    for (keys, description) in keymap.menu(Mode::Sort) {
+        lines.push(Spans::from(vec![
+            Span::raw(" "),
+            Span::styled(format!("{:<6}", keys), key_style),
+            Span::styled(description, styles.text),
+        ]));
+    }
     let message = Paragraph::new(lines).block(
         Block::default()
             .title("Sort")
@@ -589,21 +1015,39 @@ fn choose_connection<'a>(config: &Config, styles: &'a Styles) -> Paragraph<'a> {
     message
 }
 
-fn delete_confirmation_dialog<'a>(with_data: bool, name: &'a str, styles: &Styles) -> Paragraph<'a> {
-    let block = Block::default().title("Confirm").borders(Borders::ALL);
+// batch actions hit the marked torrents, also the ones hidden by the filters, and not the one under the cursor
+fn marked_targets(app: &App) -> Option<String> {
+    if marked_ids(app).is_empty() {
+        return None;
+    }
+    let ids = target_ids(app);
+    let names = describe_torrents(app, &ids);
+    let mut text = if ids.len() == 1 {
+        format!("marked 1 torrent: {}", names)
+    } else {
+        format!("marked {}", names)
+    };
+    let hidden = ids
+        .iter()
+        .filter(|id| !app.filtered_torrents.iter().any(|x| x.id == **id))
+        .count();
+    if hidden > 0 {
+        text += &format!(" ({} hidden by the filters)", hidden);
+    }
+    Some(text)
+}
+
+fn delete_confirmation_dialog<'a>(with_data: bool, name: String, styles: &Styles) -> Paragraph<'a> {
     let message = Paragraph::new(Spans::from(vec![
-        Span::styled("Sure to remove '", styles.text),
+        Span::styled("Sure to remove ", styles.text),
         Span::styled(name, styles.blend_in),
-        Span::styled("'", styles.text),
         if with_data {
             Span::styled(" with all its data?", styles.error_text)
         } else {
             Span::raw("?")
         },
-        Span::styled("[y/n]", styles.text),
     ]))
-    .wrap(Wrap { trim: false })
-    .block(block);
+    .wrap(Wrap { trim: false });
     message
 }
 fn move_dialog<B: Backend>(
@@ -617,7 +1061,76 @@ fn move_dialog<B: Backend>(
     let title = Paragraph::new(Spans::from(vec![Span::styled(name, styles.blend_in)]))
         .wrap(Wrap { trim: false });
 
-    let items: Vec<_> = folders
+    let folder_list = List::new(folder_list_items(folders, connection, styles));
+    let block = Block::default().title("Move").borders(Borders::ALL);
+    let area = centered_rect(42, 38, size);
+    let vert_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(2), Constraint::Length(15)].as_ref())
+        .split(block.inner(area));
+
+    let area = centered_rect(46, 38, size);
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+    frame.render_widget(title, vert_layout[0]);
+    frame.render_widget(folder_list, vert_layout[1]);
+}
+
+fn add_torrent_dialog<B: Backend>(
+    frame: &mut Frame<B>,
+    transition: &Transition,
+    input: &str,
+    paused: bool,
+    folders: &[(String, char, usize)],
+    connection: &Connection,
+    styles: &Styles,
+) {
+    let size = frame.size();
+    let key_style = styles.emphasis.add_modifier(Modifier::UNDERLINED);
+    let block = Block::default().title("Add Torrent").borders(Borders::ALL);
+    let area = centered_rect(46, 38, size);
+    let vert_layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(4), Constraint::Length(2), Constraint::Min(1)].as_ref())
+        .split(block.inner(area));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(block, area);
+    if transition == &Transition::AddTorrent {
+        let source = Paragraph::new(vec![
+            Spans::from(vec![Span::styled("Magnet link or path to a .torrent file:", styles.text)]),
+            Spans::from(vec![Span::styled(format!("{}▋", input), styles.emphasis)]),
+        ])
+        .wrap(Wrap { trim: false });
+        let hint = Paragraph::new(Spans::from(vec![
+            Span::styled("Enter", key_style),
+            Span::styled(" choose folder  ", styles.blend_in),
+            Span::styled("Esc", key_style),
+            Span::styled(" cancel", styles.blend_in),
+        ]));
+        frame.render_widget(source, vert_layout[0]);
+        frame.render_widget(hint, vert_layout[1]);
+    } else {
+        let source = Paragraph::new(Spans::from(vec![Span::styled(input, styles.blend_in)])).wrap(Wrap { trim: false });
+        let options = Paragraph::new(vec![
+            Spans::from(vec![
+                Span::styled("Tab", key_style),
+                Span::styled(if paused { " [x]" } else { " [ ]" }, styles.text),
+                Span::styled(" Start paused", styles.text),
+            ]),
+            Spans::from(vec![
+                Span::styled("Enter", key_style),
+                Span::styled(" default folder", styles.text),
+            ]),
+        ]);
+        frame.render_widget(source, vert_layout[0]);
+        frame.render_widget(options, vert_layout[1]);
+        frame.render_widget(List::new(folder_list_items(folders, connection, styles)), vert_layout[2]);
+    }
+}
+
+fn folder_list_items<'a>(folders: &[(String, char, usize)], connection: &Connection, styles: &Styles) -> Vec<ListItem<'a>> {
+    folders
         .iter()
         .map(|x| {
             let name = process_folder(&x.0, &connection.download_dir);
@@ -634,67 +1147,31 @@ fn move_dialog<B: Backend>(
                 Span::styled(second, styles.text),
             ]))
         })
-        .collect();
-
-    let folder_list = List::new(items);
-    let block = Block::default().title("Move").borders(Borders::ALL);
-    let area = centered_rect(42, 38, size);
-    let vert_layout = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Length(2), Constraint::Length(15)].as_ref())
-        .split(block.inner(area));
-
-    let area = centered_rect(46, 38, size);
-    frame.render_widget(Clear, area);
-    frame.render_widget(block, area);
-    frame.render_widget(title, vert_layout[0]);
-    frame.render_widget(folder_list, vert_layout[1]);
+        .collect()
 }
 
-fn help_dialog<'a>(styles: &Styles) -> Paragraph<'a> {
+// generated from the active keymap: keys of the screen help was opened from, then the global ones
+fn help_dialog<'a>(keymap: &Keymap, mode: Option<Mode>, styles: &Styles) -> Paragraph<'a> {
     let bold = styles.bold;
     let gray = styles.blend_in;
-    Paragraph::new(vec![
+    let mut lines = vec![
         Spans::from(vec![Span::raw("")]),
-        Spans::from(vec![Span::styled(
-            "Transgression TUI",
-            styles.details_emphasis,
-        )]),
-        Spans::from(""),
-        Spans::from(vec![Span::styled("↑ / k    ", bold), Span::styled("Prev item", gray)]),
-        Spans::from(vec![Span::styled("↓ / j    ", bold), Span::styled("Next item", gray)]),
-        Spans::from(vec![Span::styled("f        ", bold), Span::styled("Filter menu", gray)]),
-        Spans::from(vec![Span::styled("S        ", bold), Span::styled("Sort menu", gray)]),
-        Spans::from(vec![Span::styled("space    ", bold), Span::styled("Action menu", gray)]),
-        Spans::from(vec![
-            Span::styled("d        ", bold),
-            Span::styled("Details screen", gray),
-        ]),
-        Spans::from(vec![
-            Span::styled("/        ", bold),
-            Span::styled("Find next item in list", gray),
-        ]),
-        Spans::from(vec![
-            Span::styled("?        ", bold),
-            Span::styled("Find prev item in list", gray),
-        ]),
-        Spans::from(vec![
-            Span::styled("s        ", bold),
-            Span::styled("Search across all torrents", gray),
-        ]),
-        Spans::from(vec![
-            Span::styled("c        ", bold),
-            Span::styled("Connection menu", gray),
-        ]),
-        Spans::from(vec![Span::styled("F1       ", bold), Span::styled("Help screen", gray)]),
-        Spans::from(vec![
-            Span::styled("Esc      ", bold),
-            Span::styled("Exit from all menus", gray),
-        ]),
-        Spans::from(vec![Span::styled("q        ", bold), Span::styled("Quit", gray)]),
-        Spans::from(""),
-        Spans::from(vec![Span::raw("Configuration file: ~/.config/transg/transg-tui.toml")]),
-    ])
+        Spans::from(vec![Span::styled("Transgression TUI", styles.details_emphasis)]),
+    ];
+    for mode in mode.into_iter().chain([Mode::Global]) {
+        lines.push(Spans::from(""));
+        for (keys, description) in keymap.help(mode) {
+            lines.push(Spans::from(vec![
+                Span::styled(format!("{:<14}", keys), bold),
+                Span::styled(description, gray),
+            ]));
+        }
+    }
+    lines.push(Spans::from(""));
+    lines.push(Spans::from(vec![Span::raw(
+        "Configuration file: ~/.config/transg/transg-tui.toml",
+    )]));
+    Paragraph::new(lines)
 }
 
 fn extract_domain_name(s: &str) -> String {
@@ -706,55 +1183,413 @@ fn format_tracker_url(s: &str) -> String {
         .or_else(|| s.strip_prefix("udp://").map(extract_domain_name))
         .unwrap_or_else(|| "".to_string())
 }
-fn render_details<'a>(details: &'a TorrentDetails, styles: &Styles) -> Table<'a> {
+
+fn draw_details_screen<B: Backend>(frame: &mut Frame<B>, app: &mut App, tab: DetailsTab, area: Rect) {
+    let parts = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Min(0)].as_ref())
+        .split(area);
+    frame.render_widget(render_tabs(tab, &app.styles), parts[0]);
+    let area = parts[1];
+    if let Some(details) = &app.details {
+        match tab {
+            DetailsTab::General => frame.render_widget(render_general(details, &app.styles), area),
+            DetailsTab::Files => {
+                let visible = flatten(&app.tree_state.get_all_opened(), &app.tree_items);
+                let selected = app.tree_state.selected();
+                app.areas.tree_offset = scroll_offset(
+                    app.areas.tree_offset,
+                    visible.iter().position(|x| x.identifier == selected),
+                    visible.len(),
+                    area.height.saturating_sub(2) as usize,
+                );
+                app.areas.tree = Some(area);
+                let filter = if app.file_filter.is_empty() {
+                    String::new()
+                } else {
+                    format!(" matching \"{}\"", app.file_filter)
+                };
+                let title = format!(
+                    "Files by {}{}{}: / filter, S sort, r reverse",
+                    app.file_sort.name(),
+                    if app.file_sort_reverse { ", reversed" } else { "" },
+                    filter
+                );
+                let block = draw_tree(app.tree_items.clone(), title, &app.styles);
+                frame.render_stateful_widget(block, area, &mut app.tree_state);
+            }
+            DetailsTab::Peers => {
+                let clients = summarize_peers(&details.peers);
+                let peers_chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(3), Constraint::Length(clients.len().min(5) as u16 + 3)].as_ref())
+                    .split(area);
+                let peers = sorted_peers(details, app.peers_sort, app.peers_sort_asc);
+                let title = format!(
+                    "Peers by {} {}: S sort, r reverse",
+                    app.peers_sort.name(),
+                    if app.peers_sort_asc { "↑" } else { "↓" }
+                );
+                let table = render_peers(peers, title, &app.styles);
+                frame.render_stateful_widget(table, peers_chunks[0], &mut app.peers_state);
+                frame.render_widget(render_peer_clients(clients, &app.styles), peers_chunks[1]);
+            }
+            DetailsTab::Trackers => {
+                let trackers = render_trackers(details, &app.styles);
+                frame.render_stateful_widget(trackers, area, &mut app.trackers_state);
+            }
+            DetailsTab::Pieces => frame.render_widget(render_pieces(details, area, &app.styles), area),
+        }
+    }
+}
+
+// a summary and the file progress, details of the previous torrent aren't shown while the new ones load
+fn draw_details_pane<B: Backend>(frame: &mut Frame<B>, app: &App, area: Rect) {
+    let styles = &app.styles;
+    let selected_id = app.selected.as_ref().map(|x| x.id);
+    let details = app.details.as_ref().filter(|d| Some(d.id as i64) == selected_id);
+    let details = match details {
+        Some(d) => d,
+        None => {
+            frame.render_widget(Block::default().borders(Borders::ALL).style(styles.text), area);
+            return;
+        }
+    };
     let key_style = styles.details_emphasis;
     let value_style = styles.blend_in;
-    let rows = vec![
-        Row::new(vec![
-            Cell::from(Span::styled("Name:", key_style)),
-            Cell::from(Span::styled(details.name.clone(), value_style)),
-        ]),
-        Row::new(vec![
-            Cell::from(Span::styled("Size:", key_style)),
-            Cell::from(Span::styled(format_size(details.size_when_done as i64), value_style)),
-        ]),
-        Row::new(vec![
-            Cell::from(Span::styled("First tracker:", key_style)),
-            Cell::from(Span::styled(details.trackers.first().map_or(String::from(""), |t| format_tracker_url(&t.announce)), value_style)),
-        ]),
-        Row::new(vec![
-            Cell::from(Span::styled("Completed At:", key_style)),
-            Cell::from(Span::styled(format_time(details.done_date), value_style)),
-        ]),
-        Row::new(vec![
-            Cell::from(Span::styled("Upload Ratio:", key_style)),
-            Cell::from(Span::styled(format!("{:.2}", details.upload_ratio), value_style)),
-        ]),
-        Row::new(vec![
-            Cell::from(Span::styled("Location:", key_style)),
-            Cell::from(Span::styled(details.download_dir.clone(), value_style)),
-        ]),
-        Row::new(vec![
-            Cell::from(Span::styled("Hash:", key_style)),
-            Cell::from(Span::styled(details.hash_string.clone(), value_style)),
-        ]),
-        Row::new(vec![
-            Cell::from(Span::styled("Comment:", key_style)),
-            Cell::from(Span::styled(details.comment.clone(), value_style)),
-        ]),
-        Row::new(vec![
-            Cell::from(Span::styled("Error:", key_style)),
-            Cell::from(Span::styled(details.error_string.clone(), value_style)),
-        ]),
+    let mut fields = vec![
+        (
+            "Status:",
+            format!(
+                "{} {}",
+                format_status_text(&details.status),
+                format_percent_done(details.percent_complete)
+            ),
+        ),
+        ("Size:", format_size(details.size_when_done as i64)),
+        ("Left:", format_size(details.left_until_done as i64)),
+        (
+            "Speed:",
+            format!(
+                "↓ {} ↑ {}",
+                format_download_speed(details.rate_download as i64, false),
+                format_download_speed(details.rate_upload as i64, false)
+            ),
+        ),
+        ("Eta:", format_eta(details.eta)),
+        ("Ratio:", format!("{:.2}", details.upload_ratio)),
+        ("Peers:", details.peers_connected.to_string()),
+        ("Location:", details.download_dir.clone()),
+        ("Added At:", format_optional_time(details.added_date)),
     ];
+    if details.error != 0 {
+        fields.push(("Error:", details.error_string.clone()));
+    }
+    let parts = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(fields.len() as u16 + 2), Constraint::Min(3)].as_ref())
+        .split(area);
+    let rows: Vec<Row> = fields
+        .into_iter()
+        .map(|(key, value)| {
+            Row::new(vec![
+                Cell::from(Span::styled(key, key_style)),
+                Cell::from(Span::styled(value, value_style)),
+            ])
+        })
+        .collect();
+    let summary = Table::new(rows)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(styles.text)
+                .title(details.name.clone()),
+        )
+        .widths(&[Constraint::Length(10), Constraint::Min(10)])
+        .column_spacing(1);
+    frame.render_widget(summary, parts[0]);
+
+    // file names are relative to the torrent folder, skipped files are dimmed
+    let prefix = format!("{}/", details.name);
+    let done = details.files.iter().filter(|x| x.bytes_completed >= x.length).count();
+    let rows: Vec<Row> = details
+        .files
+        .iter()
+        .zip(&details.file_stats)
+        .map(|(f, stats)| {
+            let percent = if f.length == 0 { 1.0 } else { f.bytes_completed as f64 / f.length as f64 };
+            let style = if stats.wanted { styles.text } else { styles.blend_in };
+            Row::new(vec![
+                Cell::from(format_percent_done(percent)),
+                Cell::from(format_size(f.length as i64)),
+                Cell::from(f.name.strip_prefix(&prefix).unwrap_or(&f.name).to_string()),
+            ])
+            .style(style)
+        })
+        .collect();
+    let files = Table::new(rows)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .style(styles.text)
+                .title(format!("Files: {} of {} done", done, details.files.len())),
+        )
+        .widths(&[Constraint::Length(4), Constraint::Length(7), Constraint::Min(10)]);
+    frame.render_widget(files, parts[1]);
+}
+
+// `1 General  2 Files ...`, the number keys switch
+fn render_tabs<'a>(tab: DetailsTab, styles: &Styles) -> Tabs<'a> {
+    let titles: Vec<Spans> = DETAILS_TABS
+        .iter()
+        .enumerate()
+        .map(|(i, x)| {
+            Spans::from(vec![
+                Span::styled((i + 1).to_string(), styles.emphasis.add_modifier(Modifier::UNDERLINED)),
+                Span::styled(format!(" {}", x.title()), styles.text),
+            ])
+        })
+        .collect();
+    Tabs::new(titles)
+        .select(DETAILS_TABS.iter().position(|x| *x == tab).unwrap_or(0))
+        .style(styles.text)
+        .highlight_style(styles.highlight)
+}
+
+fn render_pieces<'a>(details: &TorrentDetails, area: Rect, styles: &Styles) -> Paragraph<'a> {
+    let pieces = decode_pieces(&details.pieces, details.piece_count);
+    let have = pieces.iter().filter(|x| *x).count();
+    let width = area.width.saturating_sub(2) as usize;
+    let height = area.height.saturating_sub(2) as usize;
+    Paragraph::new(format_piece_map(&pieces, width * height))
+        .style(styles.text)
+        .wrap(Wrap { trim: false })
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            "Pieces: {} of {} × {}, {} corrupt",
+            have,
+            details.piece_count,
+            format_size(details.piece_size as i64),
+            format_size(details.corrupt_ever as i64)
+        )))
+}
+
+// 0 means never for the daemon
+fn format_optional_time(i: u64) -> String {
+    if i == 0 {
+        String::new()
+    } else {
+        format_time(i)
+    }
+}
+
+fn format_ratio_limit(details: &TorrentDetails) -> String {
+    match details.seed_ratio_mode {
+        1 => format!("{:.2}", details.seed_ratio_limit),
+        2 => "unlimited".to_string(),
+        _ => "global".to_string(),
+    }
+}
+
+fn render_general<'a>(details: &'a TorrentDetails, styles: &Styles) -> Table<'a> {
+    let key_style = styles.details_emphasis;
+    let value_style = styles.blend_in;
+    let size = if details.total_size > details.size_when_done {
+        format!(
+            "{} of {}",
+            format_size(details.size_when_done as i64),
+            format_size(details.total_size as i64)
+        )
+    } else {
+        format_size(details.size_when_done as i64)
+    };
+    let fields = vec![
+        ("Name:", details.name.clone()),
+        (
+            "Status:",
+            format!(
+                "{} {}",
+                format_status_text(&details.status),
+                format_percent_done(details.percent_complete)
+            ),
+        ),
+        ("Location:", details.download_dir.clone()),
+        ("Size:", size),
+        ("Left:", format_size(details.left_until_done as i64)),
+        (
+            "Have:",
+            format!(
+                "{} verified, {} unchecked",
+                format_size(details.have_valid as i64),
+                format_size(details.have_unchecked as i64)
+            ),
+        ),
+        ("Downloaded:", format_size(details.downloaded_ever as i64)),
+        ("Uploaded:", format_size(details.uploaded_ever as i64)),
+        ("Corrupt:", format_size(details.corrupt_ever as i64)),
+        (
+            "Upload Ratio:",
+            format!("{:.2}, limit {}", details.upload_ratio, format_ratio_limit(details)),
+        ),
+        (
+            "Speed:",
+            format!(
+                "↓ {} ↑ {}",
+                format_download_speed(details.rate_download as i64, false),
+                format_download_speed(details.rate_upload as i64, false)
+            ),
+        ),
+        ("Eta:", format_eta(details.eta)),
+        ("Peers:", details.peers_connected.to_string()),
+        ("Queue:", details.queue_position.to_string()),
+        (
+            "Pieces:",
+            format!("{} × {}", details.piece_count, format_size(details.piece_size as i64)),
+        ),
+        ("Added At:", format_optional_time(details.added_date)),
+        ("Created At:", format_optional_time(details.date_created)),
+        ("Started At:", format_optional_time(details.start_date)),
+        ("Last Activity:", format_optional_time(details.activity_date)),
+        ("Completed At:", format_optional_time(details.done_date)),
+        (
+            "First tracker:",
+            details.trackers.first().map_or(String::from(""), |t| format_tracker_url(&t.announce)),
+        ),
+        ("Private:", if details.is_private { "yes" } else { "no" }.to_string()),
+        ("Labels:", details.labels.join(", ")),
+        ("Hash:", details.hash_string.clone()),
+        ("Creator:", details.creator.clone()),
+        ("Comment:", details.comment.clone()),
+        ("Error:", details.error_string.clone()),
+    ];
+    let rows: Vec<Row> = fields
+        .into_iter()
+        .map(|(key, value)| {
+            Row::new(vec![
+                Cell::from(Span::styled(key, key_style)),
+                Cell::from(Span::styled(value, value_style)),
+            ])
+        })
+        .collect();
+    Table::new(rows)
+        .block(Block::default().borders(Borders::ALL).style(styles.text))
+        .widths(&[Constraint::Length(17), Constraint::Min(20)])
+        .column_spacing(1)
+}
+
+fn render_trackers<'a>(details: &'a TorrentDetails, styles: &Styles) -> Table<'a> {
+    let rows: Vec<Row> = details
+        .trackers
+        .iter()
+        .map(|t| {
+            let stats = details.tracker_stats.iter().find(|s| s.id == t.id);
+            Row::new(vec![
+                Cell::from(t.tier.to_string()),
+                Cell::from(t.announce.as_str()),
+                Cell::from(stats.map_or_else(String::new, |s| s.last_announce_peer_count.to_string())),
+                Cell::from(
+                    stats
+                        .filter(|s| s.last_announce_time > 0)
+                        .map_or_else(String::new, |s| format_time(s.last_announce_time)),
+                ),
+                Cell::from(stats.map_or("", |s| s.last_announce_result.as_str())),
+            ])
+            .style(styles.text)
+        })
+        .collect();
+    Table::new(rows)
+        .header(
+            Row::new(vec!["Tier", "Announce", "Peers", "Last Announce", "Result"]).style(styles.details_emphasis),
+        )
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Trackers: a add, e edit, x remove, R replace everywhere"),
+        )
+        .highlight_style(styles.details_highlight)
+        .widths(&[
+            Constraint::Length(5),
+            Constraint::Percentage(50),
+            Constraint::Length(6),
+            Constraint::Length(20),
+            Constraint::Percentage(30),
+        ])
+        .column_spacing(1)
+}
+
+fn render_peers<'a>(peers: Vec<&Peer>, title: String, styles: &Styles) -> Table<'a> {
+    let rows: Vec<Row> = peers
+        .iter()
+        .map(|p| {
+            Row::new(vec![
+                Cell::from(p.address.clone()),
+                Cell::from(p.client_name.clone()),
+                Cell::from(format_percent_done(p.progress)),
+                Cell::from(format_download_speed(p.rate_to_client as i64, true)),
+                Cell::from(format_download_speed(p.rate_to_peer as i64, true)),
+                Cell::from(format_peer_flags(p)),
+            ])
+            .style(styles.text)
+        })
+        .collect();
+    Table::new(rows)
+        .header(
+            Row::new(vec!["Address", "Client", "Progress", "Down", "Up", "Flags"]).style(styles.details_emphasis),
+        )
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .highlight_style(styles.details_highlight)
+        .widths(&[
+            Constraint::Length(40),
+            Constraint::Length(24),
+            Constraint::Length(9),
+            Constraint::Length(11),
+            Constraint::Length(11),
+            Constraint::Min(20),
+        ])
+        .column_spacing(1)
+}
+
+fn render_peer_clients<'a>(clients: Vec<(String, usize, u64, u64)>, styles: &Styles) -> Table<'a> {
+    let rows: Vec<Row> = clients
+        .into_iter()
+        .map(|(name, count, down, up)| {
+            Row::new(vec![
+                Cell::from(name),
+                Cell::from(count.to_string()),
+                Cell::from(format_download_speed(down as i64, true)),
+                Cell::from(format_download_speed(up as i64, true)),
+            ])
+            .style(styles.text)
+        })
+        .collect();
     Table::new(rows)
-        .widths(&[Constraint::Length(17), Constraint::Length(60)])
+        .header(Row::new(vec!["Client", "Peers", "Down", "Up"]).style(styles.details_emphasis))
+        .block(Block::default().borders(Borders::ALL).title("Clients"))
+        .widths(&[
+            Constraint::Length(30),
+            Constraint::Length(6),
+            Constraint::Length(11),
+            Constraint::Length(11),
+        ])
         .column_spacing(1)
 }
 
-fn draw_tree<'a>(items: Vec<TreeItem<'a>>, styles: &Styles) -> Tree<'a> {
+fn input_title(transition: &Transition) -> Option<String> {
+    match transition {
+        Transition::EditSetting => Some("Edit".to_string()),
+        Transition::EditLabels => Some("Labels (comma separated)".to_string()),
+        Transition::RenameFile => Some("Rename".to_string()),
+        Transition::FileFilter => Some("Filter files".to_string()),
+        Transition::TrackerInput(TrackerEdit::Add) => Some("Add tracker".to_string()),
+        Transition::TrackerInput(TrackerEdit::Edit(_)) => Some("Edit tracker".to_string()),
+        Transition::TrackerInput(TrackerEdit::ReplaceFrom) => Some("Replace trackers starting with".to_string()),
+        Transition::TrackerInput(TrackerEdit::ReplaceTo(from)) => Some(format!("Replace '{}' with", from)),
+        _ => None,
+    }
+}
+
+fn draw_tree<'a>(items: Vec<TreeItem<'a>>, title: String, styles: &Styles) -> Tree<'a> {
     Tree::new(items)
-        .block(Block::default().borders(Borders::ALL).title("Files"))
+        .block(Block::default().borders(Borders::ALL).title(title))
         .highlight_style(
             styles.details_highlight
         )
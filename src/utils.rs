@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use bit_vec::BitVec;
 use chrono::{DateTime, NaiveDateTime, Utc};
 use tui_tree_widget::TreeItem;
 use crate::icons;
@@ -25,6 +26,24 @@ pub struct FileIdx {
     pub children: Vec<FileIdx>
 }
 
+// a single torrent file, with path components interned into ids
+#[derive(Debug, Clone)]
+pub struct FileEntry {
+    pub idx: usize,
+    pub length: u64,
+    pub downloaded: u64,
+    pub wanted: bool,
+    pub priority: i8,
+    pub path: Vec<u64>,
+    pub pieces: Option<FilePieces>, // none until we have the metadata
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FilePieces {
+    pub missing: u64,
+    pub shared_with_skipped: u64, // missing pieces that also belong to skipped files
+}
+
 const DEC_TB: i64 = 1000 * 1000 * 1000 * 1000;
 const DEC_GB: i64 = 1000 * 1000 * 1000;
 const DEC_MB: i64 = 1000 * 1000;
@@ -52,6 +71,112 @@ pub fn process_folder(s: &str, base_dir: &str) -> String {
     }
 }
 
+// magnets and urls are passed to the daemon as is, anything else is treated as a local .torrent file
+pub fn is_torrent_link(s: &str) -> bool {
+    s.starts_with("magnet:") || s.starts_with("http://") || s.starts_with("https://")
+}
+
+pub fn read_torrent_file(path: &str) -> std::io::Result<String> {
+    let path = match (path.strip_prefix("~/"), home::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => std::path::PathBuf::from(path),
+    };
+    let bytes = std::fs::read(path)?;
+    Ok(base64::encode(bytes))
+}
+
+// scheme and host of an announce url, e.g. "https://tracker.example.org/"
+pub fn tracker_origin(url: &str) -> String {
+    match url.find("://") {
+        Some(i) => match url[i + 3..].find('/') {
+            Some(j) => url[..i + 3 + j + 1].to_string(),
+            None => url.to_string(),
+        },
+        None => url.to_string(),
+    }
+}
+
+// transmission sends the bitfield of pieces we have, first piece in the highest bit
+pub fn decode_pieces(pieces: &str, piece_count: u64) -> BitVec {
+    let mut bits = base64::decode(pieces).map(|x| BitVec::from_bytes(&x)).unwrap_or_default();
+    bits.truncate(piece_count as usize);
+    bits
+}
+
+// squeezes pieces into `cells` characters: full, partial or missing
+pub fn format_piece_map(pieces: &BitVec, cells: usize) -> String {
+    let len = pieces.len();
+    if len == 0 || cells == 0 {
+        return "".to_string();
+    }
+    let cells = cells.min(len);
+    (0..cells)
+        .map(|i| {
+            let (from, to) = (i * len / cells, (i + 1) * len / cells);
+            let have = (from..to).filter(|x| pieces[*x]).count();
+            if have == to - from {
+                '█'
+            } else if have == 0 {
+                '░'
+            } else {
+                '▒'
+            }
+        })
+        .collect()
+}
+
+// see the flagStr description in transmission's rpc-spec
+pub fn format_peer_flags(peer: &transmission::Peer) -> String {
+    let mut flags: Vec<&str> = peer
+        .flag_str
+        .chars()
+        .filter_map(|c| match c {
+            'O' => Some("optimistic"),
+            'D' => Some("downloading"),
+            'd' => Some("want download"),
+            'U' => Some("uploading"),
+            'u' => Some("want upload"),
+            'K' => Some("not interested"),
+            '?' => Some("peer not interested"),
+            'E' => Some("encrypted"),
+            'H' => Some("DHT"),
+            'X' => Some("PEX"),
+            'I' => Some("incoming"),
+            'T' => Some("uTP"),
+            _ => None,
+        })
+        .collect();
+    if peer.client_is_choked {
+        flags.push("choked");
+    }
+    if peer.peer_is_choked {
+        flags.push("choking");
+    }
+    flags.join(", ")
+}
+
+// client name, number of peers, download and upload rates
+pub fn summarize_peers(peers: &[transmission::Peer]) -> Vec<(String, usize, u64, u64)> {
+    let mut clients: HashMap<&str, (usize, u64, u64)> = HashMap::new();
+    for peer in peers {
+        let name = if peer.client_name.is_empty() {
+            "unknown"
+        } else {
+            &peer.client_name
+        };
+        let entry = clients.entry(name).or_default();
+        entry.0 += 1;
+        entry.1 += peer.rate_to_client;
+        entry.2 += peer.rate_to_peer;
+    }
+    let mut xs: Vec<_> = clients
+        .into_iter()
+        .map(|(name, (count, down, up))| (name.to_string(), count, down, up))
+        .collect();
+    xs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    xs
+}
+
 pub fn format_percent_done(f: f64) -> String {
     if f >= 1.0 {
         "✓".to_string()
@@ -89,6 +214,27 @@ pub fn format_time(i: u64) -> String {
     datetime.format("%Y-%m-%d %H:%M:%S").to_string()
 }
 
+pub fn unix_now() -> i64 {
+    Utc::now().timestamp()
+}
+
+pub fn format_date(i: i64) -> String {
+    let naive = NaiveDateTime::from_timestamp(i, 0);
+    let datetime: DateTime<Utc> = DateTime::from_utc(naive, Utc);
+    datetime.format("%Y-%m-%d").to_string()
+}
+
+// transmission reports -1 when nothing was downloaded yet and -2 for infinity
+pub fn format_ratio(ratio: f64) -> String {
+    if ratio == -1.0 {
+        "".to_string()
+    } else if ratio == -2.0 {
+        "∞".to_string()
+    } else {
+        format!("{:.2}", ratio)
+    }
+}
+
 pub fn format_eta(secs: i64) -> String {
     if secs == -1 {
         "".to_string()
@@ -130,6 +276,18 @@ pub fn format_status<'a>(x: &TorrentStatus, err: i64) -> &'a str {
     }
 }
 
+pub fn format_status_text<'a>(x: &TorrentStatus) -> &'a str {
+    match x {
+        TorrentStatus::Paused => "Paused",
+        TorrentStatus::VerifyQueued => "Check queued",
+        TorrentStatus::Verifying => "Checking",
+        TorrentStatus::DownQueued => "Queued",
+        TorrentStatus::Downloading => "Downloading",
+        TorrentStatus::SeedQueued => "Seed queued",
+        TorrentStatus::Seeding => "Seeding",
+    }
+}
+
 #[allow(dead_code)]
 pub fn utf8_truncate(input: &mut String, maxsize: usize) {
     let mut utf8_maxsize = input.len();
@@ -209,154 +367,327 @@ pub fn build_tree(files: &[transmission::File]) -> Vec<Node> {
     xs.sort_by(|a, b| a.2[0].partial_cmp(&b.2[0]).unwrap());
     do_build_tree("", 0, xs)
 }
-// TODO: add option to disable icons
-pub fn do_build_file_tree<'a>(
-    level: usize,
-    xs: Vec<(u64, u64, Vec<u64>)>,
-    strings: &HashMap<u64, &str>,
-    add_icons: bool
-) -> Vec<TreeItem<'a>> {
-    let mut ns: Vec<TreeItem> = vec![];
+// folders show aggregated state of all the files beneath them
+fn format_file_state(xs: &[FileEntry]) -> String {
+    let wanted: Vec<&FileEntry> = xs.iter().filter(|x| x.wanted).collect();
+    if wanted.is_empty() {
+        return " [skip]".to_string();
+    }
+    let mut priorities: Vec<i8> = wanted.iter().map(|x| x.priority).collect();
+    priorities.sort_unstable();
+    priorities.dedup();
+    let priority = match priorities[..] {
+        [0] => "",
+        [1] => " [high]",
+        [-1] => " [low]",
+        _ => " [mixed]",
+    };
+    if wanted.len() < xs.len() {
+        format!(" [partial]{}{}", priority, format_pieces_state(&wanted))
+    } else {
+        priority.to_string() + &format_pieces_state(&wanted)
+    }
+}
 
-    let mut parents: Vec<u64> = xs.iter().filter(|x| x.2.len() > level).map(|x| x.2[level]).collect();
-    parents.sort();
-    parents.dedup();
+// completion by pieces: a file is done when all the pieces it touches are there
+fn format_pieces_state(xs: &[&FileEntry]) -> String {
+    let pieces: Vec<FilePieces> = xs.iter().filter_map(|x| x.pieces).collect();
+    if pieces.is_empty() {
+        return "".to_string();
+    }
+    let complete = pieces.iter().filter(|x| x.missing == 0).count();
+    let blocked = pieces.iter().filter(|x| x.shared_with_skipped > 0).count();
+    let state = match pieces[..] {
+        [p] if p.missing == 0 => " ✓".to_string(),
+        [p] => format!(" [missing {} pieces]", p.missing),
+        _ if complete == pieces.len() => " ✓".to_string(),
+        _ => format!(" [{}/{} complete]", complete, pieces.len()),
+    };
+    match pieces[..] {
+        _ if blocked == 0 => state,
+        [p] => format!("{} [{} shared with skipped]", state, p.shared_with_skipped),
+        _ => format!("{} [{} blocked by skipped]", state, blocked),
+    }
+}
 
-    for name in parents {
-        let children: Vec<(u64, u64, Vec<u64>)> = xs
-            .iter()
-            .filter(|x| x.2.len() > level && x.2[level] == name)
-            .cloned()
-            .collect();
-        let size: u64 = children.iter().map(|x| x.0).sum();
-        //let downloaded = children.iter().map(|x| x.1).sum();
-        let cs = if children.len() > 1 {
-            do_build_file_tree(level + 1, children, strings, add_icons)
+// for every file: how many of its pieces are missing and how many of those are shared with skipped files
+fn file_pieces(files: &[transmission::File], wanted: &[bool], pieces: &BitVec, piece_size: u64) -> Vec<Option<FilePieces>> {
+    if piece_size == 0 || pieces.is_empty() {
+        return vec![None; files.len()];
+    }
+    let mut ranges = vec![];
+    let mut offset = 0;
+    for f in files {
+        // pieces the file occupies, empty files don't have any
+        let range = if f.length == 0 {
+            0..0
         } else {
-            vec![]
-        };
-        let name = strings.get(&name).expect("should be name");
-        let text = if add_icons {
-        let icon = if !cs.is_empty() { icons::DEFAULT_DIR } else { 
-            match name.rsplit_once('.') {
-                        Some((_, ext)) => icons::FILE_NODE_EXTENSIONS.get(ext).unwrap_or(&icons::DEFAULT_FILE),
-                        None => icons::DEFAULT_FILE,
-            }
+            (offset / piece_size) as usize..((offset + f.length - 1) / piece_size + 1) as usize
         };
-            format!(
-                "{} {} - {}",
-                icon,
-                name,
-                crate::utils::format_size(size as i64)
-            ) 
-        } else {
-            format!(
-                "{} - {}",
-                name,
-                crate::utils::format_size(size as i64)
-            ) 
+        ranges.push(range.start.min(pieces.len())..range.end.min(pieces.len()));
+        offset += f.length;
+    }
+    let mut skipped = BitVec::from_elem(pieces.len(), false);
+    for (range, wanted) in ranges.iter().zip(wanted) {
+        if !wanted {
+            range.clone().for_each(|i| skipped.set(i, true));
+        }
+    }
+    ranges
+        .into_iter()
+        .map(|range| {
+            let missing: Vec<usize> = range.filter(|i| !pieces[*i]).collect();
+            Some(FilePieces {
+                missing: missing.len() as u64,
+                shared_with_skipped: missing.iter().filter(|i| skipped[**i]).count() as u64,
+            })
+        })
+        .collect()
+}
 
-        };
-        ns.push(TreeItem::new(
-                text,
-            //   path,
-               //  size,
-               //  downloaded,
-            cs,
-        ));
+// a file or a folder of the files tab, folders add up everything beneath them
+#[derive(Debug, Clone)]
+pub struct FileNode {
+    pub name: String,
+    pub path: String, // relative to the download dir, what the filter looks at
+    pub idx: usize,   // the file, or the first one beneath a folder
+    pub size: u64,
+    pub downloaded: u64,
+    pub state: String, // priority and pieces, see format_file_state
+    pub children: Vec<FileNode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileSort {
+    #[default]
+    Name,
+    Size,
+    Completion,
+}
+
+impl FileSort {
+    pub fn name(self) -> &'static str {
+        match self {
+            FileSort::Name => "name",
+            FileSort::Size => "size",
+            FileSort::Completion => "completion",
+        }
+    }
+
+    pub fn next(self) -> FileSort {
+        match self {
+            FileSort::Name => FileSort::Size,
+            FileSort::Size => FileSort::Completion,
+            FileSort::Completion => FileSort::Name,
+        }
     }
-    ns
 }
-pub fn build_file_tree<'a>(files: &[transmission::File], add_icons: bool) -> Vec<TreeItem<'a>> {
-    let mut id: u64 = 0;
-    let mut strings: HashMap<&str, u64> = HashMap::new();
-    let mut xs: Vec<(u64, u64, Vec<u64>)> = files
-        .iter()
-        .map(|f| {
-            (
-                f.length,
-                f.bytes_completed,
-                f.name
-                    .split('/')
-                    .map(|s| {
-                        if let Some(id) = strings.get(s) {
-                            *id
-                        } else {
-                            id += 1;
-                            strings.insert(s, id);
-                            id
-                        }
-                    })
-                    .collect(),
-            )
-        })
-        .collect();
-    xs.sort_by(|a, b| a.2[0].partial_cmp(&b.2[0]).unwrap());
-    let strings: HashMap<u64, &str> = strings.iter().map(|x| (*x.1, *x.0)).collect();
-    do_build_file_tree(0, xs, &strings, add_icons)
+
+impl FileNode {
+    fn done(&self) -> f64 {
+        if self.size == 0 {
+            1.0
+        } else {
+            self.downloaded as f64 / self.size as f64
+        }
+    }
 }
 
-// FIXME: rewrite this shit.. maybe last_parent, and cound '/'
-// shit, need to take into account parents?
-// wait, there're no parents! only list of files...
-pub fn do_build_file_tree_index(
+fn do_build_file_nodes(
     level: usize,
-    xs: Vec<(usize,  Vec<u64>)>
-) -> Vec<FileIdx> {
-    let mut ns: Vec<FileIdx> = vec![];
-    //println!("level: {:?}, xs: {:?}", level, xs);
+    xs: Vec<FileEntry>,
+    strings: &HashMap<u64, &str>,
+    files: &[transmission::File],
+) -> Vec<FileNode> {
+    let mut ns: Vec<FileNode> = vec![];
 
-    let mut parents: Vec<(usize, u64)> = xs.iter().filter(|x| x.1.len() > level).map(|x| (x.0, x.1[level])).collect();
-    parents.sort_by_key(|x| x.1);
-    parents.dedup_by_key(|x| x.1);
+    let mut parents: Vec<u64> = xs.iter().filter(|x| x.path.len() > level).map(|x| x.path[level]).collect();
+    parents.sort();
+    parents.dedup();
 
-    for (idx, name) in parents {
-        let children: Vec<(usize, Vec<u64>)> = xs
+    for name in parents {
+        let children: Vec<FileEntry> = xs
             .iter()
-            .filter(|x| x.1.len() > level && x.1[level] == name)
+            .filter(|x| x.path.len() > level && x.path[level] == name)
             .cloned()
             .collect();
-
+        let idx = children[0].idx;
+        let path = files[idx].name.split('/').take(level + 1).collect::<Vec<_>>().join("/");
+        let size = children.iter().map(|x| x.length).sum();
+        let downloaded = children.iter().map(|x| x.downloaded).sum();
+        let state = format_file_state(&children);
         let cs = if children.len() > 1 {
-            do_build_file_tree_index(level + 1, children)
+            do_build_file_nodes(level + 1, children, strings, files)
         } else {
             vec![]
         };
-        ns.push(FileIdx{
+        ns.push(FileNode {
+            name: strings.get(&name).expect("should be name").to_string(),
+            path,
             idx,
-            children:cs,
+            size,
+            downloaded,
+            state,
+            children: cs,
         });
     }
     ns
 }
-pub fn build_file_tree_index(files: &[transmission::File]) -> Vec<FileIdx> {
+
+pub fn build_file_nodes(details: &transmission::TorrentDetails) -> Vec<FileNode> {
+    let files = &details.files;
+    let file_stats = &details.file_stats;
+    let wanted: Vec<bool> = (0..files.len())
+        .map(|i| file_stats.get(i).map(|x| x.wanted).unwrap_or(true))
+        .collect();
+    let pieces = decode_pieces(&details.pieces, details.piece_count);
+    let pieces = file_pieces(files, &wanted, &pieces, details.piece_size);
     let mut id: u64 = 0;
     let mut strings: HashMap<&str, u64> = HashMap::new();
-    let mut xs: Vec<(usize, Vec<u64>)> = files
+    let mut xs: Vec<FileEntry> = files
         .iter()
         .enumerate()
-        .map(|f| {
-            (
-                f.0,
-                f.1.name
-                    .split('/')
-                    .map(|s| {
-                        if let Some(id) = strings.get(s) {
-                            *id
-                        } else {
-                            id += 1;
-                            strings.insert(s, id);
-                            id
-                        }
-                    })
-                    .collect(),
-            )
+        .map(|(i, f)| FileEntry {
+            idx: i,
+            length: f.length,
+            downloaded: f.bytes_completed,
+            wanted: wanted[i],
+            priority: file_stats.get(i).map_or(0, |x| x.priority),
+            pieces: pieces[i],
+            path: f
+                .name
+                .split('/')
+                .map(|s| {
+                    if let Some(id) = strings.get(s) {
+                        *id
+                    } else {
+                        id += 1;
+                        strings.insert(s, id);
+                        id
+                    }
+                })
+                .collect(),
         })
         .collect();
-    xs.sort_by(|a, b| a.1[0].partial_cmp(&b.1[0]).unwrap());
-    //let strings: HashMap<u64, &str> = strings.iter().map(|x| (*x.1, *x.0)).collect();
-    do_build_file_tree_index(0, xs)
+    xs.sort_by(|a, b| a.path[0].partial_cmp(&b.path[0]).unwrap());
+    let strings: HashMap<u64, &str> = strings.iter().map(|x| (*x.1, *x.0)).collect();
+    do_build_file_nodes(0, xs, &strings, files)
 }
+
+// every level on its own, names ignore case, bigger and less complete things come first
+pub fn sort_file_nodes(nodes: &mut [FileNode], sort: FileSort, reverse: bool) {
+    nodes.sort_by(|a, b| {
+        let ordering = match sort {
+            FileSort::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            FileSort::Size => b.size.cmp(&a.size),
+            FileSort::Completion => a.done().partial_cmp(&b.done()).unwrap_or(std::cmp::Ordering::Equal),
+        };
+        if reverse {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    for x in nodes.iter_mut() {
+        sort_file_nodes(&mut x.children, sort, reverse);
+    }
+}
+
+// `text` is lowercase. A matching folder keeps everything beneath it, other folders only the matches
+pub fn filter_file_nodes(nodes: Vec<FileNode>, text: &str) -> Vec<FileNode> {
+    nodes
+        .into_iter()
+        .filter_map(|mut x| {
+            if x.path.to_lowercase().contains(text) {
+                Some(x)
+            } else {
+                x.children = filter_file_nodes(x.children, text);
+                if x.children.is_empty() {
+                    None
+                } else {
+                    Some(x)
+                }
+            }
+        })
+        .collect()
+}
+
+// `██████░░  75%   1.2G  name [high]`
+pub fn file_tree_items<'a>(nodes: &[FileNode], add_icons: bool) -> Vec<TreeItem<'a>> {
+    nodes
+        .iter()
+        .map(|x| {
+            let cs = file_tree_items(&x.children, add_icons);
+            let name = if add_icons {
+                let icon = if !cs.is_empty() {
+                    icons::DEFAULT_DIR
+                } else {
+                    match x.name.rsplit_once('.') {
+                        Some((_, ext)) => icons::FILE_NODE_EXTENSIONS.get(ext).unwrap_or(&icons::DEFAULT_FILE),
+                        None => icons::DEFAULT_FILE,
+                    }
+                };
+                format!("{} {}", icon, x.name)
+            } else {
+                x.name.clone()
+            };
+            let text = format!(
+                "{} {:>4} {:>7}  {}{}",
+                format_progress_bar(x.done(), 8),
+                format!("{:.0}%", 100.0 * x.done()),
+                format_size(x.size as i64),
+                name,
+                x.state
+            );
+            TreeItem::new(text, cs)
+        })
+        .collect()
+}
+
+pub fn file_tree_index(nodes: &[FileNode]) -> Vec<FileIdx> {
+    nodes
+        .iter()
+        .map(|x| FileIdx {
+            idx: x.idx,
+            children: file_tree_index(&x.children),
+        })
+        .collect()
+}
+
+// tree identifiers by depth and first file: unlike positions they don't change when the nodes are sorted again
+pub fn file_tree_identifiers(tree: &[FileIdx]) -> HashMap<(usize, usize), Vec<usize>> {
+    fn walk(tree: &[FileIdx], parent: &[usize], acc: &mut HashMap<(usize, usize), Vec<usize>>) {
+        for (i, x) in tree.iter().enumerate() {
+            let mut identifier = parent.to_vec();
+            identifier.push(i);
+            walk(&x.children, &identifier, acc);
+            acc.insert((identifier.len(), x.idx), identifier);
+        }
+    }
+    let mut acc = HashMap::new();
+    walk(tree, &[], &mut acc);
+    acc
+}
+
+// tree identifiers of all the folders, to open everything that matched the filter
+pub fn file_tree_folders(nodes: &[FileNode], parent: &[usize]) -> Vec<Vec<usize>> {
+    let mut xs = vec![];
+    for (i, x) in nodes.iter().enumerate() {
+        if !x.children.is_empty() {
+            let mut identifier = parent.to_vec();
+            identifier.push(i);
+            xs.extend(file_tree_folders(&x.children, &identifier));
+            xs.push(identifier);
+        }
+    }
+    xs
+}
+
+pub fn format_progress_bar(f: f64, width: usize) -> String {
+    let full = ((f.clamp(0.0, 1.0) * width as f64).round() as usize).min(width);
+    "█".repeat(full) + &"░".repeat(width - full)
+}
+
 pub fn find_file_position(path: &[usize], tree: &[FileIdx]) -> Option<usize> {
     if path.is_empty() || tree.is_empty() {
         None
@@ -374,6 +705,35 @@ pub fn find_file_position(path: &[usize], tree: &[FileIdx]) -> Option<usize> {
        }
     }
 }
+
+// all files under the node, a folder node expands to every file beneath it
+pub fn find_file_indices(path: &[usize], tree: &[FileIdx]) -> Vec<usize> {
+    fn collect(node: &FileIdx, acc: &mut Vec<usize>) {
+        if node.children.is_empty() {
+            acc.push(node.idx);
+        } else {
+            node.children.iter().for_each(|c| collect(c, acc));
+        }
+    }
+    match path.split_first() {
+        Some((n, rest)) if *n < tree.len() => {
+            if rest.is_empty() {
+                let mut acc = vec![];
+                collect(&tree[*n], &mut acc);
+                acc
+            } else {
+                find_file_indices(rest, &tree[*n].children)
+            }
+        }
+        _ => vec![],
+    }
+}
+// path of a file or folder node relative to the download dir, as torrent-rename-path wants it
+pub fn find_node_path(path: &[usize], tree: &[FileIdx], files: &[transmission::File]) -> Option<String> {
+    let file = find_file_indices(path, tree).first().and_then(|i| files.get(*i))?;
+    Some(file.name.split('/').take(path.len()).collect::<Vec<_>>().join("/"))
+}
+
 //pub fn find_file_position(id:&[usize], files: &[transmission::File]) -> Option<usize> {
 //    let index = build_file_tree_index(files);
 //    do_find_position(id, &index)